@@ -1,17 +1,33 @@
+mod manifest;
+mod mirror;
 mod openxr;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+
 use ::openxr as xr;
 use glam::f32::{vec3, Mat4};
 use glow::HasContext;
-use glutin::event::{Event, VirtualKeyCode, WindowEvent};
+use glutin::event::{DeviceEvent, ElementState, Event, MouseButton, VirtualKeyCode, WindowEvent};
 use glutin::event_loop::ControlFlow;
-use glutin::platform::{
-    windows::{RawHandle, WindowExtWindows},
-    ContextTraitExt,
-};
+#[cfg(target_os = "linux")]
+use glutin::platform::unix::{EventLoopExtUnix, RawHandle, WindowExtUnix};
+#[cfg(windows)]
+use glutin::platform::windows::{RawHandle, WindowExtWindows};
+use glutin::platform::ContextTraitExt;
+#[cfg(windows)]
 use winapi::{shared::windef::HWND, um::winuser::GetDC};
+#[cfg(target_os = "linux")]
+use x11::{glx, xlib};
+
+use vr_core::{
+    FlyCamera, Model, Movement, ReferenceSpace, Scene, ShaderVersion, Viewport, XrApp, XrBackend,
+};
 
-use crate::openxr::OpenXR;
+use crate::manifest::Manifest;
+use crate::mirror::{MirrorMode, Spectator};
+use crate::openxr::{OpenXR, XrError};
 
 struct Backend {
     event_loop: glutin::event_loop::EventLoop<()>,
@@ -19,6 +35,10 @@ struct Backend {
 }
 impl Backend {
     fn new() -> Backend {
+        // OpenXR only accepts GLX contexts on Linux, so stay on X11 (or XWayland)
+        #[cfg(target_os = "linux")]
+        let el = glutin::event_loop::EventLoop::new_x11().expect("Cannot connect to X server");
+        #[cfg(not(target_os = "linux"))]
         let el = glutin::event_loop::EventLoop::new();
         let wb = glutin::window::WindowBuilder::new()
             .with_title("Hello world!")
@@ -33,6 +53,21 @@ impl Backend {
         }
     }
 
+    fn get_multiview_fn(&self) -> Option<openxr::FramebufferTextureMultiviewFn> {
+        let ptr = self
+            .windowed_context
+            .get_proc_address("glFramebufferTextureMultiviewOVR");
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe {
+                std::mem::transmute::<*const std::ffi::c_void, openxr::FramebufferTextureMultiviewFn>(
+                    ptr,
+                )
+            })
+        }
+    }
+
     fn get_gl_context(&self) -> glow::Context {
         unsafe {
             glow::Context::from_loader_function(|s| {
@@ -41,28 +76,166 @@ impl Backend {
         }
     }
 
-    fn get_xr_session_create_info(&self) -> xr::opengl::SessionCreateInfo {
+    #[cfg(windows)]
+    fn get_xr_session_create_info(&self) -> Result<xr::opengl::SessionCreateInfo, XrError> {
         let hwnd = self.windowed_context.window().hwnd();
         let h_dc = unsafe { GetDC(hwnd as HWND) };
         let handle = unsafe { self.windowed_context.raw_handle() };
         let h_glrc = match handle {
-            RawHandle::Egl(_) => panic!(),
+            RawHandle::Egl(_) => return Err(XrError::ContextUnsupported("EGL, WGL is required")),
             RawHandle::Wgl(h_glrc) => h_glrc,
         };
-        xr::opengl::SessionCreateInfo::Windows { h_dc, h_glrc }
+        Ok(xr::opengl::SessionCreateInfo::Windows { h_dc, h_glrc })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get_xr_session_create_info(&self) -> Result<xr::opengl::SessionCreateInfo, XrError> {
+        let window = self.windowed_context.window();
+        let (x_display, glx_drawable) = match (window.xlib_display(), window.xlib_window()) {
+            (Some(x_display), Some(glx_drawable)) => {
+                (x_display as *mut xlib::Display, glx_drawable)
+            }
+            _ => {
+                return Err(XrError::ContextUnsupported(
+                    "the window is not an X11 window",
+                ))
+            }
+        };
+        let handle = unsafe { self.windowed_context.raw_handle() };
+        // the openxr crate only exposes the Xlib graphics binding, so an EGL
+        // context (XR_MNDX_egl_enable, or the Wayland binding) cannot be used
+        let glx_context = match handle {
+            RawHandle::Egl(_) => return Err(XrError::ContextUnsupported("EGL, GLX is required")),
+            RawHandle::Glx(glx_context) => glx_context as glx::GLXContext,
+        };
+
+        // look up the framebuffer config and visual glutin created the context with
+        let (glx_fb_config, visualid) = unsafe {
+            let mut screen = 0;
+            let mut fb_config_id = 0;
+            let queried = [
+                glx::glXQueryContext(x_display, glx_context, glx::GLX_SCREEN, &mut screen),
+                glx::glXQueryContext(
+                    x_display,
+                    glx_context,
+                    glx::GLX_FBCONFIG_ID,
+                    &mut fb_config_id,
+                ),
+            ];
+            if queried.iter().any(|&result| result != xlib::Success as i32) {
+                return Err(XrError::ContextUnsupported("cannot query the GLX context"));
+            }
+
+            let attribs = [glx::GLX_FBCONFIG_ID, fb_config_id, 0];
+            let mut count = 0;
+            let configs = glx::glXChooseFBConfig(x_display, screen, attribs.as_ptr(), &mut count);
+            if configs.is_null() || count == 0 {
+                return Err(XrError::ContextUnsupported(
+                    "no GLX framebuffer config of the context",
+                ));
+            }
+            let glx_fb_config = *configs;
+            xlib::XFree(configs as *mut _);
+
+            let visual_info = glx::glXGetVisualFromFBConfig(x_display, glx_fb_config);
+            if visual_info.is_null() {
+                return Err(XrError::ContextUnsupported(
+                    "the GLX framebuffer config has no visual",
+                ));
+            }
+            let visualid = (*visual_info).visualid as u32;
+            xlib::XFree(visual_info as *mut _);
+
+            (glx_fb_config, visualid)
+        };
+
+        Ok(xr::opengl::SessionCreateInfo::Xlib {
+            x_display: x_display as *mut _,
+            visualid,
+            glx_fb_config: glx_fb_config as *mut _,
+            glx_drawable,
+            glx_context: glx_context as *mut _,
+        })
     }
 }
 
 fn main() {
     let backend = Backend::new();
-    let gl = backend.get_gl_context();
+    let gl = Rc::new(backend.get_gl_context());
+    unsafe {
+        gl.enable(glow::DEPTH_TEST);
+    }
 
-    let session_create_info = backend.get_xr_session_create_info();
-    let mut xr = OpenXR::new(session_create_info);
+    let scene = Rc::new(RefCell::new(Scene::new(&*gl, ShaderVersion::Gl410)));
+
+    if let Some(path) =
+        std::env::args().find_map(|arg| arg.strip_prefix("--model=").map(String::from))
+    {
+        match Model::load(&path) {
+            Ok(model) => unsafe {
+                scene.borrow_mut().add_model(
+                    &*gl,
+                    &model,
+                    Mat4::from_translation(vec3(0.0, 0.0, -1.5)),
+                );
+            },
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let manifest =
+        match std::env::args().find_map(|arg| arg.strip_prefix("--actions=").map(String::from)) {
+            Some(path) => Manifest::load(&path).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1);
+            }),
+            None => Manifest::builtin(),
+        };
+
+    let mut xr = match backend
+        .get_xr_session_create_info()
+        .and_then(|session_create_info| OpenXR::new(session_create_info, &manifest))
+    {
+        Ok(mut xr) => {
+            if std::env::args().any(|arg| arg == "--multiview") {
+                match backend.get_multiview_fn() {
+                    Some(framebuffer_texture_multiview) => {
+                        xr.set_multiview(framebuffer_texture_multiview)
+                    }
+                    None => println!("glFramebufferTextureMultiviewOVR unavailable"),
+                }
+            }
+            let sample_count =
+                std::env::args().find_map(|arg| arg.strip_prefix("--samples=")?.parse().ok());
+            if let Some(sample_count) = sample_count {
+                xr.set_sample_count(sample_count);
+            }
+            if std::env::args().any(|arg| arg == "--seated") {
+                match xr.set_reference_spaces(&ReferenceSpace::SEATED) {
+                    Ok(reference_space) => println!("reference space: {:?}", reference_space),
+                    Err(e) => eprintln!("XR: {}", e),
+                }
+            }
+            xr.start(gl.clone(), scene.clone());
+            Some(xr)
+        }
+        Err(e) => {
+            eprintln!("XR: {}, falling back to desktop-only rendering", e);
+            None
+        }
+    };
 
-    let mut scene = Scene::new(&gl);
+    let mut mirror_mode = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--mirror=").and_then(MirrorMode::from_arg))
+        .unwrap_or(MirrorMode::LeftEye);
+    let mut spectator = Spectator::new();
 
-    let swapchain_framebuffer = unsafe { gl.create_framebuffer() }.unwrap();
+    let mut camera = FlyCamera::new(vec3(0.0, 1.0, 3.0), vec3(0.0, 0.0, 0.0));
+    let mut mouse_look = false;
+    let mut last_frame = Instant::now();
 
     backend.event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
@@ -77,62 +250,121 @@ fn main() {
                 {
                     *control_flow = ControlFlow::Exit
                 }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.virtual_keycode == Some(VirtualKeyCode::M)
+                        && input.state == ElementState::Pressed =>
+                {
+                    mirror_mode = mirror_mode.next();
+                    println!("mirror mode {:?}", mirror_mode);
+                }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.virtual_keycode == Some(VirtualKeyCode::R)
+                        && input.state == ElementState::Pressed =>
+                {
+                    if let Some(Err(e)) = xr.as_mut().map(|xr| xr.recenter()) {
+                        eprintln!("XR: {}", e);
+                    }
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let Some(movement) = input.virtual_keycode.and_then(movement_key) {
+                        camera.set_moving(movement, input.state == ElementState::Pressed);
+                    }
+                }
+                WindowEvent::MouseInput {
+                    button: MouseButton::Right,
+                    state,
+                    ..
+                } => {
+                    mouse_look = *state == ElementState::Pressed;
+                }
+                WindowEvent::MouseInput {
+                    button: MouseButton::Left,
+                    state,
+                    ..
+                } => {
+                    camera.set_select(*state == ElementState::Pressed);
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    let size = backend.windowed_context.window().inner_size();
+                    camera.set_cursor(
+                        position.x as _,
+                        position.y as _,
+                        size.width as _,
+                        size.height as _,
+                    );
+                }
                 _ => (),
             },
-            Event::LoopDestroyed => {
-                return;
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } if mouse_look => {
+                camera.look(delta.0 as _, delta.1 as _);
             }
+            Event::LoopDestroyed => (),
             Event::MainEventsCleared => {
-                xr.process_events(control_flow);
                 backend.windowed_context.window().request_redraw();
             }
             Event::RedrawRequested(_) => {
-                xr.wait_frame(|session, views, interaction, xr_frame_state, swapchains| {
-                    scene.update(session, interaction, xr_frame_state);
-
-                    for (i, swapchain) in swapchains.iter_mut().enumerate() {
-                        let view = views[i];
-                        unsafe {
-                            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
-
-                            let images = swapchain.handle.enumerate_images().unwrap();
-                            let image_id = swapchain.handle.acquire_image().unwrap();
-                            swapchain.handle.wait_image(xr::Duration::INFINITE).unwrap();
-                            let image = images[image_id as usize];
-                            let color_texture: glow::Texture = std::mem::transmute(image);
-
-                            let rect = swapchain.rect;
-                            gl.viewport(
-                                rect.offset.x,
-                                rect.offset.y,
-                                rect.extent.width,
-                                rect.extent.height,
-                            );
-                            gl.framebuffer_texture_2d(
-                                glow::FRAMEBUFFER,
-                                glow::COLOR_ATTACHMENT0,
-                                glow::TEXTURE_2D,
-                                Some(color_texture),
-                                0,
-                            );
+                let now = Instant::now();
+                let dt = (now - last_frame).as_secs_f32();
+                camera.update(dt);
+                last_frame = now;
+
+                let size = backend.windowed_context.window().inner_size();
+                let viewport = Viewport {
+                    x: 0,
+                    y: 0,
+                    width: size.width as _,
+                    height: size.height as _,
+                };
 
-                            scene.v_mat = openxr::pose_transform_matrix(view.pose).inverse();
-                            scene.p_mat =
-                                openxr::fov_perspective_projection_matrix(view.fov, 0.1, 100.0);
-
-                            scene.render(&gl);
+                unsafe {
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+                    gl.clear(glow::COLOR_BUFFER_BIT);
+                }
 
-                            swapchain.handle.release_image().unwrap();
+                if let Some(session) = &mut xr {
+                    session.mirror = Some((mirror_mode, viewport));
+                    match session.try_poll() {
+                        Ok(true) => {}
+                        Ok(false) => *control_flow = ControlFlow::Exit,
+                        Err(e) => {
+                            eprintln!("XR: {}, falling back to desktop-only rendering", e);
+                            xr = None;
                         }
                     }
-                });
+                }
 
-                unsafe {
-                    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
-                    let size = backend.windowed_context.window().inner_size();
-                    gl.viewport(0, 0, size.width as _, size.height as _);
+                let mut scene = scene.borrow_mut();
+                let head_pose = xr
+                    .as_ref()
+                    .filter(|xr| xr.is_running())
+                    .and_then(|xr| xr.head_pose());
+                let desktop_view = match head_pose {
+                    Some(head_pose) => {
+                        spectator.update(head_pose, dt);
+                        if mirror_mode == MirrorMode::Spectator {
+                            Some(spectator.view(viewport))
+                        } else {
+                            None
+                        }
+                    }
+                    None => {
+                        scene.update(&camera.input_state(viewport));
+                        // no controller to buzz on the desktop
+                        scene.take_haptics();
+                        Some(camera.view(viewport))
+                    }
+                };
 
-                    scene.render(&gl);
+                if let Some(view) = desktop_view {
+                    unsafe {
+                        gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                        scene.clear(&*gl);
+                        scene.render_view(&*gl, &view);
+                    }
                 }
 
                 backend.windowed_context.swap_buffers().unwrap();
@@ -142,183 +374,14 @@ fn main() {
     });
 }
 
-struct Scene {
-    program: glow::NativeProgram,
-    vbo: glow::NativeBuffer,
-    p_mat: Mat4,
-    v_mat: Mat4,
-    mid_m_mat: Mat4,
-    left_m_mat: Option<Mat4>,
-    right_m_mat: Option<Mat4>,
-}
-impl Scene {
-    fn new(gl: &glow::Context) -> Scene {
-        let program = unsafe {
-            let program = gl.create_program().expect("Cannot create program");
-
-            let shader_sources = [
-                (
-                    glow::VERTEX_SHADER,
-                    r#"#version 410
-                    uniform mat4 mvp;
-                    in vec3 Position;
-                    void main() {
-                        gl_Position = mvp * vec4(Position, 1);
-                    }"#,
-                ),
-                (
-                    glow::FRAGMENT_SHADER,
-                    r#"#version 410
-                    uniform vec3 color;
-                    out vec4 FragColor;
-                    void main() {
-                        FragColor = vec4(color, 1);
-                    } "#,
-                ),
-            ];
-
-            let mut shaders = Vec::with_capacity(shader_sources.len());
-
-            for (shader_type, shader_source) in shader_sources.iter() {
-                let shader = gl
-                    .create_shader(*shader_type)
-                    .expect("Cannot create shader");
-                gl.shader_source(shader, shader_source);
-                gl.compile_shader(shader);
-                if !gl.get_shader_compile_status(shader) {
-                    panic!("{}", gl.get_shader_info_log(shader));
-                }
-                gl.attach_shader(program, shader);
-                shaders.push(shader);
-            }
-
-            gl.link_program(program);
-            if !gl.get_program_link_status(program) {
-                panic!("{}", gl.get_program_info_log(program));
-            }
-
-            for shader in shaders {
-                gl.detach_shader(program, shader);
-                gl.delete_shader(shader);
-            }
-
-            program
-        };
-
-        let vbo = unsafe {
-            let vertices = [0.0f32, 1.0, 0.0, 1.0, 0.0, 0.0, -1.0, 0.0, 0.0];
-            let vertices_u8: &[u8] = core::slice::from_raw_parts(
-                vertices.as_ptr() as *const u8,
-                vertices.len() * core::mem::size_of::<f32>(),
-            );
-
-            let vbo = gl.create_buffer().unwrap();
-            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
-            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices_u8, glow::STATIC_DRAW);
-
-            let vao = gl.create_vertex_array().unwrap();
-            gl.bind_vertex_array(Some(vao));
-            gl.enable_vertex_attrib_array(0);
-            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 12, 0);
-
-            vbo
-        };
-
-        Scene {
-            program,
-            vbo,
-            p_mat: Mat4::perspective_rh_gl(std::f32::consts::PI / 2.0, 1.0, 0.1, 100.0),
-            v_mat: Mat4::look_at_rh(
-                vec3(0.0, 1.0, 3.0),
-                vec3(0.0, 0.0, 0.0),
-                vec3(0.0, 1.0, 0.0),
-            ),
-            mid_m_mat: Mat4::from_translation(vec3(0.0, 0.0, -3.0)),
-            left_m_mat: None,
-            right_m_mat: None,
-        }
-    }
-
-    fn update(
-        &mut self,
-        session: &xr::Session<xr::OpenGL>,
-        interaction: &openxr::Interaction,
-        xr_frame_state: &xr::FrameState,
-    ) {
-        let left_location = interaction
-            .left_space
-            .locate(&interaction.stage, xr_frame_state.predicted_display_time)
-            .unwrap();
-        if interaction
-            .left_action
-            .is_active(session, xr::Path::NULL)
-            .unwrap()
-        {
-            self.left_m_mat = Some(
-                openxr::pose_transform_matrix(left_location.pose)
-                    * Mat4::from_scale(vec3(0.1, 0.1, 0.1))
-                    * Mat4::from_rotation_x(-std::f32::consts::PI / 2.0),
-            );
-        }
-
-        let right_location = interaction
-            .right_space
-            .locate(&interaction.stage, xr_frame_state.predicted_display_time)
-            .unwrap();
-        if interaction
-            .right_action
-            .is_active(session, xr::Path::NULL)
-            .unwrap()
-        {
-            self.right_m_mat = Some(
-                openxr::pose_transform_matrix(right_location.pose)
-                    * Mat4::from_scale(vec3(0.1, 0.1, 0.1))
-                    * Mat4::from_rotation_x(-std::f32::consts::PI / 2.0),
-            );
-        }
-    }
-
-    unsafe fn render(&self, gl: &glow::Context) {
-        gl.use_program(Some(self.program));
-        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
-
-        gl.clear_color(0.0, 0.0, 0.0, 1.0);
-        gl.clear(glow::COLOR_BUFFER_BIT);
-
-        // mid triangle
-        let mvp_mat = self.p_mat * self.v_mat * self.mid_m_mat;
-        let uniform_location = gl.get_uniform_location(self.program, "mvp");
-        gl.uniform_matrix_4_f32_slice(uniform_location.as_ref(), false, &mvp_mat.to_cols_array());
-        let uniform_location = gl.get_uniform_location(self.program, "color");
-        gl.uniform_3_f32(uniform_location.as_ref(), 1.0, 1.0, 1.0);
-        gl.draw_arrays(glow::TRIANGLES, 0, 3);
-
-        // left triangle
-        if let Some(left_m_mat) = self.left_m_mat {
-            let mvp_mat: Mat4 = self.p_mat * self.v_mat * left_m_mat;
-            let uniform_location = gl.get_uniform_location(self.program, "mvp");
-            gl.uniform_matrix_4_f32_slice(
-                uniform_location.as_ref(),
-                false,
-                &mvp_mat.to_cols_array(),
-            );
-            let uniform_location = gl.get_uniform_location(self.program, "color");
-            gl.uniform_3_f32(uniform_location.as_ref(), 1.0, 0.0, 0.0);
-            gl.draw_arrays(glow::TRIANGLES, 0, 3);
-        }
-
-        // right triangle
-        if let Some(right_m_mat) = self.right_m_mat {
-            let mvp_mat: Mat4 = self.p_mat * self.v_mat * right_m_mat;
-            let uniform_location = gl.get_uniform_location(self.program, "mvp");
-            gl.uniform_matrix_4_f32_slice(
-                uniform_location.as_ref(),
-                false,
-                &mvp_mat.to_cols_array(),
-            );
-            let uniform_location = gl.get_uniform_location(self.program, "color");
-            gl.uniform_3_f32(uniform_location.as_ref(), 0.0, 1.0, 0.0);
-            gl.draw_arrays(glow::TRIANGLES, 0, 3);
-        }
+fn movement_key(key: VirtualKeyCode) -> Option<Movement> {
+    match key {
+        VirtualKeyCode::W => Some(Movement::Forward),
+        VirtualKeyCode::S => Some(Movement::Backward),
+        VirtualKeyCode::A => Some(Movement::Left),
+        VirtualKeyCode::D => Some(Movement::Right),
+        VirtualKeyCode::E | VirtualKeyCode::Space => Some(Movement::Up),
+        VirtualKeyCode::Q | VirtualKeyCode::LShift => Some(Movement::Down),
+        _ => None,
     }
 }
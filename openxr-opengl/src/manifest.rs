@@ -0,0 +1,491 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Manifest compiled into the binary, used unless `--actions=<file>` is given.
+pub const BUILTIN: &str = include_str!("../actions.json");
+
+/// Top level user paths actions may be filtered by.
+const SUBACTION_PATHS: [&str; 5] = [
+    "/user/hand/left",
+    "/user/hand/right",
+    "/user/head",
+    "/user/gamepad",
+    "/user/treadmill",
+];
+
+/// Actions the input code reads into [`vr_core::ControllerState`], with the
+/// type each must be declared with. They are read per hand, so each must
+/// have both [`HAND_PATHS`] among its subaction paths. Manifests may declare
+/// others as well.
+pub const STANDARD_ACTIONS: [(&str, ActionType); 10] = [
+    ("grip", ActionType::Pose),
+    ("aim", ActionType::Pose),
+    ("select", ActionType::Boolean),
+    ("menu", ActionType::Boolean),
+    ("primary", ActionType::Boolean),
+    ("secondary", ActionType::Boolean),
+    ("trigger", ActionType::Float),
+    ("squeeze", ActionType::Float),
+    ("thumbstick", ActionType::Vector2f),
+    ("haptic", ActionType::Haptic),
+];
+
+/// Subaction paths the standard actions are read and located with.
+const HAND_PATHS: [&str; 2] = ["/user/hand/left", "/user/hand/right"];
+
+// XR_MAX_ACTION_SET_NAME_SIZE and XR_MAX_ACTION_NAME_SIZE, without the nul
+const MAX_NAME_LEN: usize = 63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionType {
+    Boolean,
+    Float,
+    Vector2f,
+    Pose,
+    Haptic,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub action_sets: Vec<ActionSet>,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionSet {
+    pub name: String,
+    pub localized_name: String,
+    #[serde(default)]
+    pub priority: u32,
+    pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Action {
+    pub name: String,
+    pub localized_name: String,
+    #[serde(rename = "type")]
+    pub ty: ActionType,
+    #[serde(default)]
+    pub subaction_paths: Vec<String>,
+}
+
+/// Suggested bindings for one interaction profile.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub path: String,
+    pub bindings: Vec<Binding>,
+}
+
+/// A relative `path` is bound below every subaction path of the action,
+/// an absolute one only where it points.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Binding {
+    pub action: String,
+    pub path: String,
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    InvalidName(String),
+    DuplicateName(String),
+    InvalidSubactionPath {
+        action: String,
+        path: String,
+    },
+    InvalidProfilePath(String),
+    UnknownAction {
+        profile: String,
+        action: String,
+    },
+    InvalidBindingPath {
+        profile: String,
+        path: String,
+        reason: &'static str,
+    },
+    WrongType {
+        action: String,
+        expected: ActionType,
+        found: ActionType,
+    },
+    MissingHandPath {
+        action: String,
+        path: &'static str,
+    },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Io(e) => write!(f, "cannot read action manifest: {}", e),
+            ManifestError::Parse(e) => write!(f, "malformed action manifest: {}", e),
+            ManifestError::InvalidName(name) => write!(
+                f,
+                "invalid name {:?}: use 1 to {} characters out of a-z, 0-9, '-', '_' and '.'",
+                name, MAX_NAME_LEN
+            ),
+            ManifestError::DuplicateName(name) => write!(f, "{:?} is declared twice", name),
+            ManifestError::InvalidSubactionPath { action, path } => write!(
+                f,
+                "action {:?} has subaction path {:?}, expected one of {}",
+                action,
+                path,
+                SUBACTION_PATHS.join(", ")
+            ),
+            ManifestError::InvalidProfilePath(path) => write!(
+                f,
+                "invalid interaction profile {:?}, expected /interaction_profiles/<vendor>/<device>",
+                path
+            ),
+            ManifestError::UnknownAction { profile, action } => {
+                write!(f, "{} binds undeclared action {:?}", profile, action)
+            }
+            ManifestError::InvalidBindingPath {
+                profile,
+                path,
+                reason,
+            } => write!(f, "{} binds invalid path {:?}: {}", profile, path, reason),
+            ManifestError::WrongType {
+                action,
+                expected,
+                found,
+            } => write!(
+                f,
+                "action {:?} must be of type {:?}, not {:?}",
+                action, expected, found
+            ),
+            ManifestError::MissingHandPath { action, path } => write!(
+                f,
+                "action {:?} is read per hand and needs subaction path {:?}",
+                action, path
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl Manifest {
+    pub fn builtin() -> Manifest {
+        Manifest::parse(BUILTIN).expect("built-in action manifest is invalid")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Manifest, ManifestError> {
+        let text = std::fs::read_to_string(path).map_err(ManifestError::Io)?;
+        Manifest::parse(&text)
+    }
+
+    /// Parses and validates a JSON manifest.
+    pub fn parse(text: &str) -> Result<Manifest, ManifestError> {
+        let manifest: Manifest = serde_json::from_str(text).map_err(ManifestError::Parse)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &Action> {
+        self.action_sets.iter().flat_map(|set| set.actions.iter())
+    }
+
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.actions().find(|action| action.name == name)
+    }
+
+    /// Checks everything the runtime would reject, plus the types of the
+    /// standard actions. Action names are unique across sets so bindings
+    /// can refer to them by name alone.
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        let mut set_names = HashSet::new();
+        let mut action_names = HashSet::new();
+        for set in &self.action_sets {
+            check_name(&set.name)?;
+            if !set_names.insert(&set.name) {
+                return Err(ManifestError::DuplicateName(set.name.clone()));
+            }
+            for action in &set.actions {
+                check_name(&action.name)?;
+                if !action_names.insert(&action.name) {
+                    return Err(ManifestError::DuplicateName(action.name.clone()));
+                }
+                for path in &action.subaction_paths {
+                    if !SUBACTION_PATHS.contains(&path.as_str()) {
+                        return Err(ManifestError::InvalidSubactionPath {
+                            action: action.name.clone(),
+                            path: path.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (name, expected) in STANDARD_ACTIONS {
+            if let Some(action) = self.action(name) {
+                if action.ty != expected {
+                    return Err(ManifestError::WrongType {
+                        action: name.to_owned(),
+                        expected,
+                        found: action.ty,
+                    });
+                }
+                // the runtime rejects reading an action with a subaction
+                // path it was not created with
+                if let Some(path) = HAND_PATHS
+                    .into_iter()
+                    .find(|path| !action.subaction_paths.iter().any(|p| p == path))
+                {
+                    return Err(ManifestError::MissingHandPath {
+                        action: name.to_owned(),
+                        path,
+                    });
+                }
+            }
+        }
+
+        let mut profile_paths = HashSet::new();
+        for profile in &self.profiles {
+            let segments = profile.path.split('/').collect::<Vec<_>>();
+            let valid = segments.len() == 4
+                && segments[0].is_empty()
+                && segments[1] == "interaction_profiles"
+                && segments[2..].iter().all(|segment| is_path_segment(segment));
+            if !valid {
+                return Err(ManifestError::InvalidProfilePath(profile.path.clone()));
+            }
+            if !profile_paths.insert(&profile.path) {
+                return Err(ManifestError::DuplicateName(profile.path.clone()));
+            }
+            for binding in &profile.bindings {
+                let action =
+                    self.action(&binding.action)
+                        .ok_or_else(|| ManifestError::UnknownAction {
+                            profile: profile.path.clone(),
+                            action: binding.action.clone(),
+                        })?;
+                for path in binding_paths(action, &binding.path) {
+                    check_binding_path(action, &path).map_err(|reason| {
+                        ManifestError::InvalidBindingPath {
+                            profile: profile.path.clone(),
+                            path,
+                            reason,
+                        }
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Full paths a binding resolves to.
+pub fn binding_paths(action: &Action, path: &str) -> Vec<String> {
+    if path.starts_with('/') || action.subaction_paths.is_empty() {
+        vec![path.to_owned()]
+    } else {
+        action
+            .subaction_paths
+            .iter()
+            .map(|top_level| format!("{}/{}", top_level, path))
+            .collect()
+    }
+}
+
+fn check_name(name: &str) -> Result<(), ManifestError> {
+    if name.len() <= MAX_NAME_LEN && is_path_segment(name) {
+        Ok(())
+    } else {
+        Err(ManifestError::InvalidName(name.to_owned()))
+    }
+}
+
+fn is_path_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && segment
+            .chars()
+            .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '-' | '_' | '.'))
+}
+
+fn check_binding_path(action: &Action, path: &str) -> Result<(), &'static str> {
+    let segments = match path.strip_prefix("/user/") {
+        Some(rest) => rest.split('/').collect::<Vec<_>>(),
+        None => return Err("must start with /user/ or be relative to a subaction path"),
+    };
+    if !segments.iter().all(|segment| is_path_segment(segment)) {
+        return Err("path segments may only contain a-z, 0-9, '-', '_' and '.'");
+    }
+    if !action.subaction_paths.is_empty()
+        && !action
+            .subaction_paths
+            .iter()
+            .any(|top_level| path.starts_with(&format!("{}/", top_level)))
+    {
+        return Err("not below any of the action's subaction paths");
+    }
+    match action.ty {
+        ActionType::Haptic if !segments.contains(&"output") => {
+            Err("haptic actions bind to .../output/...")
+        }
+        ActionType::Haptic => Ok(()),
+        _ if !segments.contains(&"input") => Err("input actions bind to .../input/..."),
+        ActionType::Pose if !path.ends_with("/pose") => Err("pose actions bind to .../pose"),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One action set holding `actions`, bound by `bindings` for the
+    /// simple controller.
+    fn manifest(actions: &str, bindings: &str) -> Result<Manifest, ManifestError> {
+        Manifest::parse(&format!(
+            r#"{{
+                "action_sets": [{{
+                    "name": "gameplay",
+                    "localized_name": "Gameplay",
+                    "actions": [{}]
+                }}],
+                "profiles": [{{
+                    "path": "/interaction_profiles/khr/simple_controller",
+                    "bindings": [{}]
+                }}]
+            }}"#,
+            actions, bindings
+        ))
+    }
+
+    const HANDS: &str = r#""subaction_paths": ["/user/hand/left", "/user/hand/right"]"#;
+
+    #[test]
+    fn builtin_manifest_is_valid() {
+        let manifest = Manifest::builtin();
+        for (name, ty) in STANDARD_ACTIONS {
+            assert_eq!(manifest.action(name).map(|action| action.ty), Some(ty));
+        }
+        assert!(!manifest.profiles.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_subaction_path() {
+        let result = manifest(
+            r#"{"name": "select", "localized_name": "Select", "type": "boolean",
+                "subaction_paths": ["/user/hand/middle"]}"#,
+            "",
+        );
+        assert!(matches!(
+            result,
+            Err(ManifestError::InvalidSubactionPath { action, path })
+                if action == "select" && path == "/user/hand/middle"
+        ));
+    }
+
+    #[test]
+    fn rejects_binding_to_unknown_action() {
+        let result = manifest(
+            &format!(
+                r#"{{"name": "select", "localized_name": "Select", "type": "boolean", {}}}"#,
+                HANDS
+            ),
+            r#"{"action": "jump", "path": "/user/hand/left/input/select/click"}"#,
+        );
+        assert!(matches!(
+            result,
+            Err(ManifestError::UnknownAction { action, .. }) if action == "jump"
+        ));
+    }
+
+    #[test]
+    fn rejects_standard_action_of_wrong_type() {
+        let result = manifest(
+            r#"{"name": "trigger", "localized_name": "Trigger", "type": "boolean"}"#,
+            "",
+        );
+        assert!(matches!(
+            result,
+            Err(ManifestError::WrongType {
+                expected: ActionType::Float,
+                found: ActionType::Boolean,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_standard_action_missing_a_hand() {
+        let result = manifest(
+            r#"{"name": "squeeze", "localized_name": "Squeeze", "type": "float",
+                "subaction_paths": ["/user/hand/left"]}"#,
+            "",
+        );
+        assert!(matches!(
+            result,
+            Err(ManifestError::MissingHandPath { action, path: "/user/hand/right" })
+                if action == "squeeze"
+        ));
+        // other actions may leave out the hands
+        let result = manifest(
+            r#"{"name": "jump", "localized_name": "Jump", "type": "boolean"}"#,
+            r#"{"action": "jump", "path": "/user/hand/left/input/select/click"}"#,
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn expands_relative_binding_per_subaction_path() {
+        let manifest = manifest(
+            &format!(
+                r#"{{"name": "select", "localized_name": "Select", "type": "boolean", {}}}"#,
+                HANDS
+            ),
+            r#"{"action": "select", "path": "input/select/click"}"#,
+        )
+        .unwrap();
+        let select = manifest.action("select").unwrap();
+        assert_eq!(
+            binding_paths(select, "input/select/click"),
+            [
+                "/user/hand/left/input/select/click",
+                "/user/hand/right/input/select/click"
+            ]
+        );
+        assert_eq!(
+            binding_paths(select, "/user/hand/left/input/select/click"),
+            ["/user/hand/left/input/select/click"]
+        );
+    }
+
+    #[test]
+    fn rejects_haptic_action_bound_to_input() {
+        let result = manifest(
+            &format!(
+                r#"{{"name": "haptic", "localized_name": "Haptic", "type": "haptic", {}}}"#,
+                HANDS
+            ),
+            r#"{"action": "haptic", "path": "input/select/click"}"#,
+        );
+        assert!(matches!(
+            result,
+            Err(ManifestError::InvalidBindingPath { path, .. })
+                if path == "/user/hand/left/input/select/click"
+        ));
+        let valid = manifest(
+            &format!(
+                r#"{{"name": "haptic", "localized_name": "Haptic", "type": "haptic", {}}}"#,
+                HANDS
+            ),
+            r#"{"action": "haptic", "path": "output/haptic"}"#,
+        );
+        assert!(valid.is_ok());
+    }
+}
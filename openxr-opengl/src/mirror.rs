@@ -0,0 +1,107 @@
+use glam::f32::{vec3, Mat4, Vec3};
+use vr_core::{View, Viewport};
+
+/// What the desktop window shows while the headset is presenting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorMode {
+    /// Blit of the left eye image.
+    LeftEye,
+    /// Blit of both eye images side by side.
+    BothEyes,
+    /// Scene rendered from a smoothed camera behind the user's head.
+    Spectator,
+}
+impl MirrorMode {
+    pub fn from_arg(arg: &str) -> Option<MirrorMode> {
+        match arg {
+            "left" => Some(MirrorMode::LeftEye),
+            "both" => Some(MirrorMode::BothEyes),
+            "spectator" => Some(MirrorMode::Spectator),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> MirrorMode {
+        match self {
+            MirrorMode::LeftEye => MirrorMode::BothEyes,
+            MirrorMode::BothEyes => MirrorMode::Spectator,
+            MirrorMode::Spectator => MirrorMode::LeftEye,
+        }
+    }
+
+    /// Window rect the given eye is blitted to, or `None` if it isn't shown.
+    pub fn eye_rect(
+        self,
+        eye: usize,
+        width: i32,
+        height: i32,
+        window: Viewport,
+    ) -> Option<Viewport> {
+        match (self, eye) {
+            (MirrorMode::LeftEye, 0) => Some(fit_rect(width, height, window)),
+            (MirrorMode::BothEyes, 0 | 1) => {
+                let half = window.width / 2;
+                let target = Viewport {
+                    x: window.x + half * eye as i32,
+                    width: half,
+                    ..window
+                };
+                Some(fit_rect(width, height, target))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Largest rect with the source aspect ratio centered in `target`.
+pub fn fit_rect(width: i32, height: i32, target: Viewport) -> Viewport {
+    let scale = (target.width as f32 / width as f32).min(target.height as f32 / height as f32);
+    let fit_width = (width as f32 * scale) as i32;
+    let fit_height = (height as f32 * scale) as i32;
+    Viewport {
+        x: target.x + (target.width - fit_width) / 2,
+        y: target.y + (target.height - fit_height) / 2,
+        width: fit_width,
+        height: fit_height,
+    }
+}
+
+/// Third-person camera trailing the head with exponential smoothing.
+pub struct Spectator {
+    position: Vec3,
+    target: Vec3,
+    /// Offset from the head in its yaw frame.
+    pub offset: Vec3,
+    /// Higher is snappier, in 1/seconds.
+    pub stiffness: f32,
+}
+impl Spectator {
+    pub fn new() -> Spectator {
+        Spectator {
+            position: vec3(0.0, 2.0, 2.0),
+            target: vec3(0.0, 1.5, 0.0),
+            offset: vec3(0.0, 0.5, 1.5),
+            stiffness: 3.0,
+        }
+    }
+
+    pub fn update(&mut self, head: Mat4, dt: f32) {
+        let (_, rotation, head_position) = head.to_scale_rotation_translation();
+        let forward = rotation * -Vec3::Z;
+        let yaw = (-forward.x).atan2(-forward.z);
+        let desired = head_position + Mat4::from_rotation_y(yaw).transform_vector3(self.offset);
+
+        let t = 1.0 - (-self.stiffness * dt).exp();
+        self.position = self.position.lerp(desired, t);
+        self.target = self.target.lerp(head_position, t);
+    }
+
+    pub fn view(&self, viewport: Viewport) -> View {
+        let aspect = viewport.width.max(1) as f32 / viewport.height.max(1) as f32;
+        View {
+            viewport,
+            v_mat: Mat4::look_at_rh(self.position, self.target, Vec3::Y),
+            p_mat: Mat4::perspective_rh_gl(std::f32::consts::PI / 3.0, aspect, 0.1, 100.0),
+        }
+    }
+}
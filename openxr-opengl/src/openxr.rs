@@ -1,21 +1,359 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use glow::HasContext;
 use openxr as xr;
+use vr_core::{
+    level_pose, Button, ControllerState, FrameView, Hand, HandGestures, HandSkeleton, HapticPulse,
+    InputState, Joint, ReferenceSpace, SharedApp, View, Viewport, XrBackend,
+};
+
+use crate::manifest::{self, ActionType, Manifest};
+use crate::mirror::MirrorMode;
 
 const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
 
+pub const Z_NEAR: f32 = 0.1;
+pub const Z_FAR: f32 = 100.0;
+
+/// Depth formats in order of preference.
+const DEPTH_FORMATS: [u32; 4] = [
+    glow::DEPTH_COMPONENT24,
+    glow::DEPTH_COMPONENT32F,
+    glow::DEPTH24_STENCIL8,
+    glow::DEPTH_COMPONENT16,
+];
+
+#[derive(Debug)]
+pub enum XrError {
+    /// No OpenXR runtime is installed or it could not be loaded.
+    RuntimeMissing,
+    /// The runtime has no head mounted display, usually because none is plugged in.
+    FormFactorUnavailable,
+    ExtensionMissing(&'static str),
+    FormatUnsupported(u32),
+    /// The session or instance was lost and has to be recreated.
+    SessionLost,
+    /// None of the preferred reference spaces is supported.
+    ReferenceSpaceUnavailable,
+    /// The window's GL context cannot be handed to the runtime.
+    ContextUnsupported(&'static str),
+    Runtime(xr::sys::Result),
+}
+impl std::fmt::Display for XrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XrError::RuntimeMissing => write!(f, "no OpenXR runtime available"),
+            XrError::FormFactorUnavailable => write!(f, "head mounted display unavailable"),
+            XrError::ExtensionMissing(name) => write!(f, "extension {} unsupported", name),
+            XrError::FormatUnsupported(format) => {
+                write!(f, "swapchain format {:#x} unsupported", format)
+            }
+            XrError::SessionLost => write!(f, "session lost"),
+            XrError::ReferenceSpaceUnavailable => write!(f, "no preferred reference space"),
+            XrError::ContextUnsupported(reason) => write!(f, "GL context unsupported: {}", reason),
+            XrError::Runtime(result) => write!(f, "runtime error {}", result),
+        }
+    }
+}
+impl std::error::Error for XrError {}
+impl From<xr::sys::Result> for XrError {
+    fn from(result: xr::sys::Result) -> XrError {
+        match result {
+            xr::sys::Result::ERROR_RUNTIME_UNAVAILABLE | xr::sys::Result::ERROR_RUNTIME_FAILURE => {
+                XrError::RuntimeMissing
+            }
+            xr::sys::Result::ERROR_FORM_FACTOR_UNAVAILABLE
+            | xr::sys::Result::ERROR_FORM_FACTOR_UNSUPPORTED => XrError::FormFactorUnavailable,
+            xr::sys::Result::ERROR_SESSION_LOST | xr::sys::Result::ERROR_INSTANCE_LOST => {
+                XrError::SessionLost
+            }
+            result => XrError::Runtime(result),
+        }
+    }
+}
+
+/// `glFramebufferTextureMultiviewOVR`, loaded from the GL context.
+pub type FramebufferTextureMultiviewFn = unsafe extern "system" fn(
+    target: u32,
+    attachment: u32,
+    texture: u32,
+    level: i32,
+    base_view_index: i32,
+    num_views: i32,
+);
+
 pub struct Swapchain {
     pub handle: xr::Swapchain<xr::OpenGL>,
+    pub images: Vec<u32>,
     pub rect: xr::Rect2Di,
+    /// Array layers, one per view in multiview mode.
+    pub layers: u32,
+    pub depth: Depth,
+    /// MSAA sample count of the intermediate render target, 1 renders directly
+    /// into the swapchain images.
+    pub samples: u32,
+    /// Multisampled render target resolved into the swapchain images.
+    pub msaa: Option<glow::Framebuffer>,
+}
+
+pub enum Depth {
+    /// Runtime-visible depth images, submitted when `depth_layer` is enabled.
+    Swapchain {
+        handle: xr::Swapchain<xr::OpenGL>,
+        images: Vec<u32>,
+        format: u32,
+    },
+    /// Private depth texture for runtimes without a depth swapchain format.
+    Texture(Option<glow::Texture>),
+}
+impl Depth {
+    fn format(&self) -> u32 {
+        match self {
+            Depth::Swapchain { format, .. } => *format,
+            Depth::Texture(_) => glow::DEPTH_COMPONENT24,
+        }
+    }
+
+    fn attachment(format: u32) -> u32 {
+        if format == glow::DEPTH24_STENCIL8 {
+            glow::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            glow::DEPTH_ATTACHMENT
+        }
+    }
+}
+
+/// An action of any type, as declared in the action manifest.
+pub enum Action {
+    Boolean(xr::Action<bool>),
+    Float(xr::Action<f32>),
+    Vector2f(xr::Action<xr::Vector2f>),
+    Pose(xr::Action<xr::Posef>),
+    Haptic(xr::Action<xr::Haptic>),
 }
 
 pub struct Interaction {
-    pub action_set: xr::ActionSet,
-    pub right_action: xr::Action<xr::Posef>,
-    pub left_action: xr::Action<xr::Posef>,
-    pub right_space: xr::Space,
-    pub left_space: xr::Space,
+    pub action_sets: Vec<xr::ActionSet>,
+    /// Keyed by the manifest's action names.
+    pub actions: HashMap<String, Action>,
+    pub left_path: xr::Path,
+    pub right_path: xr::Path,
+    /// Left and right spaces of the `grip` and `aim` actions, as declared.
+    hand_spaces: HashMap<&'static str, [xr::Space; 2]>,
+    /// Left and right, present with `XR_EXT_hand_tracking`.
+    hand_trackers: Option<[xr::HandTracker; 2]>,
+    /// Space every pose is located in, see [`OpenXR::set_reference_spaces`].
     pub stage: xr::Space,
 }
 
+impl Interaction {
+    /// Creates and attaches the manifest's action sets, suggesting its
+    /// bindings. `hand_tracking` needs `XR_EXT_hand_tracking` enabled.
+    pub fn new(
+        instance: &xr::Instance,
+        session: &xr::Session<xr::OpenGL>,
+        manifest: &Manifest,
+        stage: xr::Space,
+        hand_tracking: bool,
+    ) -> Result<Interaction, XrError> {
+        let mut action_sets = Vec::new();
+        let mut actions = HashMap::new();
+        for set in &manifest.action_sets {
+            let action_set =
+                instance.create_action_set(&set.name, &set.localized_name, set.priority)?;
+            for action in &set.actions {
+                let paths = action
+                    .subaction_paths
+                    .iter()
+                    .map(|path| instance.string_to_path(path))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let (name, localized_name) = (&action.name, &action.localized_name);
+                let created = match action.ty {
+                    ActionType::Boolean => {
+                        Action::Boolean(action_set.create_action(name, localized_name, &paths)?)
+                    }
+                    ActionType::Float => {
+                        Action::Float(action_set.create_action(name, localized_name, &paths)?)
+                    }
+                    ActionType::Vector2f => {
+                        Action::Vector2f(action_set.create_action(name, localized_name, &paths)?)
+                    }
+                    ActionType::Pose => {
+                        Action::Pose(action_set.create_action(name, localized_name, &paths)?)
+                    }
+                    ActionType::Haptic => {
+                        Action::Haptic(action_set.create_action(name, localized_name, &paths)?)
+                    }
+                };
+                actions.insert(action.name.clone(), created);
+            }
+            action_sets.push(action_set);
+        }
+
+        for profile in &manifest.profiles {
+            let mut suggested = Vec::new();
+            for binding in &profile.bindings {
+                let declared = manifest.action(&binding.action).unwrap();
+                for path in manifest::binding_paths(declared, &binding.path) {
+                    let path = instance.string_to_path(&path)?;
+                    suggested.push(match &actions[&binding.action] {
+                        Action::Boolean(action) => xr::Binding::new(action, path),
+                        Action::Float(action) => xr::Binding::new(action, path),
+                        Action::Vector2f(action) => xr::Binding::new(action, path),
+                        Action::Pose(action) => xr::Binding::new(action, path),
+                        Action::Haptic(action) => xr::Binding::new(action, path),
+                    });
+                }
+            }
+            let profile_path = instance.string_to_path(&profile.path)?;
+            // a runtime may not know every profile, which is fine
+            if let Err(e) = instance.suggest_interaction_profile_bindings(profile_path, &suggested)
+            {
+                println!("no bindings for {}: {}", profile.path, e);
+            }
+        }
+
+        session.attach_action_sets(&action_sets.iter().collect::<Vec<_>>())?;
+
+        let left_path = instance.string_to_path(xr::USER_HAND_LEFT)?;
+        let right_path = instance.string_to_path(xr::USER_HAND_RIGHT)?;
+        let mut hand_spaces = HashMap::new();
+        for name in ["grip", "aim"] {
+            if let Some(Action::Pose(action)) = actions.get(name) {
+                let spaces = [
+                    action.create_space(session.clone(), left_path, xr::Posef::IDENTITY)?,
+                    action.create_space(session.clone(), right_path, xr::Posef::IDENTITY)?,
+                ];
+                hand_spaces.insert(name, spaces);
+            }
+        }
+
+        let hand_trackers = if hand_tracking {
+            Some([
+                session.create_hand_tracker(xr::Hand::LEFT)?,
+                session.create_hand_tracker(xr::Hand::RIGHT)?,
+            ])
+        } else {
+            None
+        };
+
+        Ok(Interaction {
+            action_sets,
+            actions,
+            left_path,
+            right_path,
+            hand_spaces,
+            hand_trackers,
+            stage,
+        })
+    }
+
+    pub fn sync(&self, session: &xr::Session<xr::OpenGL>) -> Result<(), XrError> {
+        let active = self
+            .action_sets
+            .iter()
+            .map(xr::ActiveActionSet::from)
+            .collect::<Vec<_>>();
+        session.sync_actions(&active)?;
+        Ok(())
+    }
+
+    /// Reads the standard actions, see [`manifest::STANDARD_ACTIONS`].
+    /// Ones the manifest leaves out read as released.
+    pub fn input_state(
+        &self,
+        session: &xr::Session<xr::OpenGL>,
+        xr_frame_state: &xr::FrameState,
+    ) -> Result<InputState, XrError> {
+        let controller = |hand: Hand| {
+            let path = self.path(hand);
+            let pose = |name: &str| match (self.actions.get(name), self.hand_spaces.get(name)) {
+                (Some(Action::Pose(action)), Some(spaces))
+                    if action.is_active(session, path)? =>
+                {
+                    let location = spaces[hand as usize]
+                        .locate(&self.stage, xr_frame_state.predicted_display_time)?;
+                    Ok::<_, XrError>(Some(pose_transform_matrix(location.pose)))
+                }
+                _ => Ok(None),
+            };
+            let button = |name: &str| match self.actions.get(name) {
+                Some(Action::Boolean(action)) => {
+                    let state = action.state(session, path)?;
+                    Ok::<_, XrError>(Button {
+                        pressed: state.current_state,
+                        changed: state.changed_since_last_sync,
+                    })
+                }
+                _ => Ok(Button::default()),
+            };
+            let float = |name: &str| match self.actions.get(name) {
+                Some(Action::Float(action)) => {
+                    Ok::<_, XrError>(action.state(session, path)?.current_state)
+                }
+                _ => Ok(0.0),
+            };
+            let thumbstick = match self.actions.get("thumbstick") {
+                Some(Action::Vector2f(action)) => {
+                    let state = action.state(session, path)?.current_state;
+                    glam::vec2(state.x, state.y)
+                }
+                _ => glam::Vec2::ZERO,
+            };
+            let skeleton = match &self.hand_trackers {
+                Some(trackers) => self
+                    .stage
+                    .locate_hand_joints(
+                        &trackers[hand as usize],
+                        xr_frame_state.predicted_display_time,
+                    )?
+                    .and_then(|locations| hand_skeleton(&locations)),
+                None => None,
+            };
+            Ok::<_, XrError>(ControllerState {
+                grip: pose("grip")?,
+                aim: pose("aim")?,
+                select: button("select")?,
+                menu: button("menu")?,
+                primary: button("primary")?,
+                secondary: button("secondary")?,
+                trigger: float("trigger")?,
+                squeeze: float("squeeze")?,
+                thumbstick,
+                skeleton,
+            })
+        };
+        Ok(InputState {
+            left: controller(Hand::Left)?,
+            right: controller(Hand::Right)?,
+            ..Default::default()
+        })
+    }
+
+    pub fn apply_haptic(
+        &self,
+        session: &xr::Session<xr::OpenGL>,
+        pulse: &HapticPulse,
+    ) -> Result<(), XrError> {
+        if let Some(Action::Haptic(action)) = self.actions.get("haptic") {
+            let vibration = xr::HapticVibration::new()
+                .amplitude(pulse.amplitude)
+                .duration(xr::Duration::from_nanos((pulse.duration * 1e9) as i64))
+                .frequency(pulse.frequency.unwrap_or(xr::FREQUENCY_UNSPECIFIED));
+            action.apply_feedback(session, self.path(pulse.hand), &vibration)?;
+        }
+        Ok(())
+    }
+
+    fn path(&self, hand: Hand) -> xr::Path {
+        match hand {
+            Hand::Left => self.left_path,
+            Hand::Right => self.right_path,
+        }
+    }
+}
+
 pub struct OpenXR {
     system: xr::SystemId,
     instance: xr::Instance,
@@ -25,14 +363,34 @@ pub struct OpenXR {
     frame_stream: xr::FrameStream<xr::OpenGL>,
     environment_blend_mode: xr::EnvironmentBlendMode,
     interaction: Interaction,
+    gestures: HandGestures,
+    depth_layer: bool,
     event_storage: xr::EventDataBuffer,
     swapchains: Option<Vec<Swapchain>>,
+    swapchain_framebuffer: Option<glow::Framebuffer>,
+    multiview: Option<FramebufferTextureMultiviewFn>,
+    sample_count: Option<u32>,
+    app: Option<(Rc<glow::Context>, SharedApp<glow::Context>)>,
+    exit: bool,
+    /// Eye images are blitted into this rect of the default framebuffer.
+    pub mirror: Option<(MirrorMode, Viewport)>,
+    head_pose: Option<glam::f32::Mat4>,
+    /// Display time of the previous rendered frame, for `InputState::dt`.
+    last_display_time: Option<xr::Time>,
+    /// Type of `interaction.stage`, to pick out its change events.
+    space_type: xr::ReferenceSpaceType,
+    /// Pose of `interaction.stage` in the runtime's space of that type,
+    /// moved off identity by [`OpenXR::recenter`].
+    space_offset: glam::f32::Mat4,
 }
 impl OpenXR {
-    pub fn new(session_create_info: xr::opengl::SessionCreateInfo) -> OpenXR {
+    pub fn new(
+        session_create_info: xr::opengl::SessionCreateInfo,
+        manifest: &Manifest,
+    ) -> Result<OpenXR, XrError> {
         let entry = xr::Entry::linked();
 
-        let instance = {
+        let (instance, depth_layer, hand_tracking_extension) = {
             let app_info = xr::ApplicationInfo {
                 application_name: "hello openxrs",
                 ..Default::default()
@@ -40,34 +398,35 @@ impl OpenXR {
 
             let extensions = entry
                 .enumerate_extensions()
-                .expect("Cannot enumerate extensions");
+                .map_err(|_| XrError::RuntimeMissing)?;
             if !extensions.khr_opengl_enable {
-                panic!("XR: OpenGL extension unsupported");
+                return Err(XrError::ExtensionMissing("XR_KHR_opengl_enable"));
             }
 
             let mut extension_set = xr::ExtensionSet::default();
             extension_set.khr_opengl_enable = true;
+            extension_set.khr_composition_layer_depth = extensions.khr_composition_layer_depth;
+            extension_set.ext_hand_tracking = extensions.ext_hand_tracking;
 
-            entry
-                .create_instance(&app_info, &extension_set, &[])
-                .unwrap()
+            (
+                entry.create_instance(&app_info, &extension_set, &[])?,
+                extensions.khr_composition_layer_depth,
+                extensions.ext_hand_tracking,
+            )
         };
 
-        let instance_props = instance.properties().unwrap();
+        let instance_props = instance.properties()?;
         println!(
             "loaded instance: {} v{}",
             instance_props.runtime_name, instance_props.runtime_version
         );
 
-        let system = instance
-            .system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)
-            .unwrap();
+        let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
 
-        let environment_blend_mode = instance
-            .enumerate_environment_blend_modes(system, VIEW_TYPE)
-            .unwrap()[0];
+        let environment_blend_mode =
+            instance.enumerate_environment_blend_modes(system, VIEW_TYPE)?[0];
 
-        let system_props = instance.system_properties(system).unwrap();
+        let system_props = instance.system_properties(system)?;
         println!(
             "selected system {}: {}",
             system_props.system_id.into_raw(),
@@ -78,60 +437,22 @@ impl OpenXR {
             }
         );
 
-        let _reqs = <xr::OpenGL as xr::Graphics>::requirements(&instance, system).unwrap();
-
-        let (session, frame_wait, frame_stream) =
-            unsafe { instance.create_session::<xr::OpenGL>(system, &session_create_info) }.unwrap();
-
-        let action_set = instance
-            .create_action_set("input", "input pose information", 0)
-            .unwrap();
-
-        let right_action = action_set
-            .create_action::<xr::Posef>("right_hand", "Right Hand Controller", &[])
-            .unwrap();
-        let left_action = action_set
-            .create_action::<xr::Posef>("left_hand", "Left Hand Controller", &[])
-            .unwrap();
-
-        instance
-            .suggest_interaction_profile_bindings(
-                instance
-                    .string_to_path("/interaction_profiles/khr/simple_controller")
-                    .unwrap(),
-                &[
-                    xr::Binding::new(
-                        &right_action,
-                        instance
-                            .string_to_path("/user/hand/right/input/grip/pose")
-                            .unwrap(),
-                    ),
-                    xr::Binding::new(
-                        &left_action,
-                        instance
-                            .string_to_path("/user/hand/left/input/grip/pose")
-                            .unwrap(),
-                    ),
-                ],
-            )
-            .unwrap();
+        let hand_tracking = hand_tracking_extension && instance.supports_hand_tracking(system)?;
+        println!("hand tracking: {}", hand_tracking);
 
-        session.attach_action_sets(&[&action_set]).unwrap();
+        let _reqs = <xr::OpenGL as xr::Graphics>::requirements(&instance, system)?;
 
-        let right_space = right_action
-            .create_space(session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)
-            .unwrap();
-        let left_space = left_action
-            .create_space(session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)
-            .unwrap();
+        let (session, frame_wait, frame_stream) =
+            unsafe { instance.create_session::<xr::OpenGL>(system, &session_create_info) }?;
 
-        let stage = session
-            .create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)
-            .unwrap();
+        let (reference_space, stage) =
+            create_reference_space(&session, &ReferenceSpace::ROOM_SCALE)?;
+        println!("reference space: {:?}", reference_space);
+        let interaction = Interaction::new(&instance, &session, manifest, stage, hand_tracking)?;
 
         let event_storage = xr::EventDataBuffer::new();
 
-        OpenXR {
+        Ok(OpenXR {
             system,
             instance,
             session,
@@ -139,42 +460,77 @@ impl OpenXR {
             frame_wait,
             frame_stream,
             environment_blend_mode,
-            interaction: Interaction {
-                action_set,
-                right_action,
-                left_action,
-                right_space,
-                left_space,
-                stage,
-            },
+            interaction,
+            gestures: HandGestures::default(),
+            depth_layer,
             event_storage,
             swapchains: None,
-        }
+            swapchain_framebuffer: None,
+            multiview: None,
+            sample_count: None,
+            app: None,
+            exit: false,
+            mirror: None,
+            head_pose: None,
+            last_display_time: None,
+            space_type: space_type(reference_space).unwrap(),
+            space_offset: glam::f32::Mat4::IDENTITY,
+        })
     }
 
-    pub fn process_events(&mut self, control_flow: &mut glutin::event_loop::ControlFlow) {
-        while let Some(event) = self.instance.poll_event(&mut self.event_storage).unwrap() {
+    fn process_events(&mut self) -> Result<(), XrError> {
+        while let Some(event) = self.instance.poll_event(&mut self.event_storage)? {
             use xr::Event::*;
             match event {
                 SessionStateChanged(e) => {
                     println!("entered state {:?}", e.state());
                     match e.state() {
                         xr::SessionState::READY => {
-                            self.session.begin(VIEW_TYPE).unwrap();
+                            self.session.begin(VIEW_TYPE)?;
                             self.session_running = true;
                         }
                         xr::SessionState::STOPPING => {
-                            self.session.end().unwrap();
+                            self.session.end()?;
                             self.session_running = false;
                         }
-                        xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
-                            *control_flow = glutin::event_loop::ControlFlow::Exit;
+                        xr::SessionState::EXITING => {
+                            self.exit = true;
+                        }
+                        xr::SessionState::LOSS_PENDING => {
+                            return Err(XrError::SessionLost);
                         }
                         _ => {}
                     };
                 }
                 InstanceLossPending(_) => {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                    return Err(XrError::SessionLost);
+                }
+                InteractionProfileChanged(_) => {
+                    for hand in [Hand::Left, Hand::Right] {
+                        let top_level = self.interaction.path(hand);
+                        let profile = self.session.current_interaction_profile(top_level)?;
+                        let profile = if profile == xr::Path::NULL {
+                            None
+                        } else {
+                            Some(self.instance.path_to_string(profile)?)
+                        };
+                        println!("{:?} hand bound to {:?}", hand, profile);
+                    }
+                }
+                ReferenceSpaceChangePending(e) if e.reference_space_type() == self.space_type => {
+                    println!("reference space {:?} changed", e.reference_space_type());
+                    // the event moves the runtime's space, ours sits at an
+                    // offset in it and moves along
+                    let offset = self.space_offset;
+                    let previous_from_new = e.pose_valid().then(|| {
+                        offset.inverse()
+                            * pose_transform_matrix(e.pose_in_previous_space())
+                            * offset
+                    });
+                    if let Some((_, app)) = &self.app {
+                        app.borrow_mut().reference_space_changed(previous_from_new);
+                    }
+                    self.report_play_area()?;
                 }
                 EventsLost(e) => {
                     println!("lost {} events", e.lost_event_count());
@@ -182,132 +538,717 @@ impl OpenXR {
                 _ => {}
             };
         }
+        Ok(())
     }
 
-    pub fn wait_frame(
-        &mut self,
-        mut frame_fn: impl FnMut(
-            &xr::Session<xr::OpenGL>,
-            &Vec<xr::View>,
-            &Interaction,
-            &xr::FrameState,
-            &mut Vec<Swapchain>,
-        ),
-    ) {
-        if !self.session_running {
-            std::thread::sleep(std::time::Duration::from_millis(1000 / 60));
-            return;
+    /// Renders both eyes in one pass into a single two-layer swapchain.
+    /// Only takes effect before the first frame and if the context supports
+    /// `GL_OVR_multiview2`, otherwise each eye keeps its own swapchain.
+    pub fn set_multiview(&mut self, framebuffer_texture_multiview: FramebufferTextureMultiviewFn) {
+        if self.swapchains.is_none() {
+            self.multiview = Some(framebuffer_texture_multiview);
         }
+    }
 
-        let xr_frame_state = self.frame_wait.wait().unwrap();
+    /// MSAA samples per pixel, clamped to what the runtime supports. Defaults
+    /// to the runtime's recommendation. Only takes effect before the first frame.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = Some(sample_count);
+    }
 
-        self.frame_stream.begin().unwrap();
+    /// Tracks poses in the first supported space of `preferences` instead
+    /// of the room-scale default, returning the one picked.
+    pub fn set_reference_spaces(
+        &mut self,
+        preferences: &[ReferenceSpace],
+    ) -> Result<ReferenceSpace, XrError> {
+        let (reference_space, stage) = create_reference_space(&self.session, preferences)?;
+        self.interaction.stage = stage;
+        self.space_type = space_type(reference_space).unwrap();
+        self.space_offset = glam::f32::Mat4::IDENTITY;
+        self.report_play_area()?;
+        Ok(reference_space)
+    }
 
-        if !xr_frame_state.should_render {
-            self.frame_stream
-                .end(
-                    xr_frame_state.predicted_display_time,
-                    self.environment_blend_mode,
-                    &[],
-                )
-                .unwrap();
-            return;
+    /// Rebuilds the tracking space as a LOCAL space with its origin at the
+    /// head, facing where the head faces. Does nothing before the first frame.
+    pub fn recenter(&mut self) -> Result<(), XrError> {
+        let time = match self.last_display_time {
+            Some(time) => time,
+            None => return Ok(()),
+        };
+        let local = self
+            .session
+            .create_reference_space(xr::ReferenceSpaceType::LOCAL, xr::Posef::IDENTITY)?;
+        let view = self
+            .session
+            .create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?;
+        let location = view.locate(&local, time)?;
+        let valid =
+            xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID;
+        if !location.location_flags.contains(valid) {
+            return Ok(());
         }
+        let offset = level_pose(pose_transform_matrix(location.pose));
+        let (_, rotation, translation) = offset.to_scale_rotation_translation();
+        let pose = xr::Posef {
+            orientation: xr::Quaternionf {
+                x: rotation.x,
+                y: rotation.y,
+                z: rotation.z,
+                w: rotation.w,
+            },
+            position: xr::Vector3f {
+                x: translation.x,
+                y: translation.y,
+                z: translation.z,
+            },
+        };
+        self.interaction.stage = self
+            .session
+            .create_reference_space(xr::ReferenceSpaceType::LOCAL, pose)?;
+        self.space_type = xr::ReferenceSpaceType::LOCAL;
+        self.space_offset = offset;
+        self.report_play_area()
+    }
 
-        let swapchains = self.swapchains.get_or_insert_with(|| {
-            let view_configuration_views = self
-                .instance
-                .enumerate_view_configuration_views(self.system, VIEW_TYPE)
-                .unwrap();
-            view_configuration_views
-                .into_iter()
-                .map(|vp| {
-                    let width = vp.recommended_image_rect_width;
-                    let height = vp.recommended_image_rect_height;
-                    let rect = xr::Rect2Di {
-                        offset: xr::Offset2Di { x: 0, y: 0 },
-                        extent: xr::Extent2Di {
-                            width: width as _,
-                            height: height as _,
-                        },
-                    };
+    /// Hands the app the stage's bounds rectangle, or nothing when the
+    /// tracking space is not the stage or the runtime does not know them.
+    fn report_play_area(&self) -> Result<(), XrError> {
+        let app = match &self.app {
+            Some((_, app)) => app,
+            None => return Ok(()),
+        };
+        let bounds = if self.space_type == xr::ReferenceSpaceType::STAGE {
+            self.session.reference_space_bounds_rect(self.space_type)?
+        } else {
+            None
+        };
+        let polygon = match bounds {
+            // centered on the stage origin, width along X and height along Z
+            Some(bounds) => {
+                let (x, z) = (bounds.width / 2.0, bounds.height / 2.0);
+                vec![
+                    glam::vec2(-x, -z),
+                    glam::vec2(x, -z),
+                    glam::vec2(x, z),
+                    glam::vec2(-x, z),
+                ]
+            }
+            None => Vec::new(),
+        };
+        app.borrow_mut().play_area_changed(&polygon);
+        Ok(())
+    }
 
-                    let sample_count = vp.recommended_swapchain_sample_count;
+    fn create_swapchains(&self) -> Result<Vec<Swapchain>, XrError> {
+        let swapchain_formats = self.session.enumerate_swapchain_formats()?;
+        if !swapchain_formats.contains(&glow::SRGB8_ALPHA8) {
+            return Err(XrError::FormatUnsupported(glow::SRGB8_ALPHA8));
+        }
 
-                    let swapchain_formats = self.session.enumerate_swapchain_formats().unwrap();
-                    if !swapchain_formats.contains(&glow::SRGB8_ALPHA8) {
-                        panic!("XR: Cannot use OpenGL GL_SRGB8_ALPHA8 swapchain format");
-                    }
+        let depth_format = DEPTH_FORMATS
+            .into_iter()
+            .find(|format| swapchain_formats.contains(format));
+
+        let mut view_configuration_views = self
+            .instance
+            .enumerate_view_configuration_views(self.system, VIEW_TYPE)?;
+        let layers = if self.multiview.is_some() {
+            let layers = view_configuration_views.len() as u32;
+            view_configuration_views.truncate(1);
+            layers
+        } else {
+            1
+        };
+
+        view_configuration_views
+            .into_iter()
+            .map(|vp| {
+                let width = vp.recommended_image_rect_width;
+                let height = vp.recommended_image_rect_height;
+                let rect = xr::Rect2Di {
+                    offset: xr::Offset2Di { x: 0, y: 0 },
+                    extent: xr::Extent2Di {
+                        width: width as _,
+                        height: height as _,
+                    },
+                };
+
+                let samples = self
+                    .sample_count
+                    .unwrap_or(vp.recommended_swapchain_sample_count)
+                    .min(vp.max_swapchain_sample_count)
+                    .max(1);
+                // multisampled array textures are out of reach through glow
+                let samples = if layers > 1 { 1 } else { samples };
+
+                let handle = self.session.create_swapchain(&xr::SwapchainCreateInfo {
+                    create_flags: xr::SwapchainCreateFlags::EMPTY,
+                    usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT
+                        | xr::SwapchainUsageFlags::SAMPLED,
+                    format: glow::SRGB8_ALPHA8,
+                    sample_count: 1,
+                    width,
+                    height,
+                    face_count: 1,
+                    array_size: layers,
+                    mip_count: 1,
+                })?;
+
+                let images = handle.enumerate_images()?;
 
-                    let handle = self
-                        .session
-                        .create_swapchain(&xr::SwapchainCreateInfo {
+                let depth = match depth_format {
+                    Some(format) => {
+                        let handle = self.session.create_swapchain(&xr::SwapchainCreateInfo {
                             create_flags: xr::SwapchainCreateFlags::EMPTY,
-                            usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT
-                                | xr::SwapchainUsageFlags::SAMPLED,
-                            format: glow::SRGB8_ALPHA8,
-                            sample_count,
+                            usage_flags: xr::SwapchainUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                            format,
+                            sample_count: 1,
                             width,
                             height,
                             face_count: 1,
-                            array_size: 1,
+                            array_size: layers,
                             mip_count: 1,
-                        })
-                        .unwrap();
+                        })?;
+                        let images = handle.enumerate_images()?;
+                        Depth::Swapchain {
+                            handle,
+                            images,
+                            format,
+                        }
+                    }
+                    None => Depth::Texture(None),
+                };
 
-                    Swapchain { handle, rect }
+                Ok(Swapchain {
+                    handle,
+                    images,
+                    rect,
+                    layers,
+                    depth,
+                    samples,
+                    msaa: None,
                 })
-                .collect::<Vec<_>>()
-        });
+            })
+            .collect()
+    }
 
-        self.session
-            .sync_actions(&[(&self.interaction.action_set).into()])
-            .unwrap();
+    fn wait_frame(&mut self) -> Result<(), XrError> {
+        let (gl, app) = match &self.app {
+            Some(app) => app,
+            None => return Ok(()),
+        };
 
-        let (_flags, views) = self
-            .session
-            .locate_views(
-                VIEW_TYPE,
-                xr_frame_state.predicted_display_time,
-                &self.interaction.stage,
-            )
-            .unwrap();
-
-        frame_fn(
-            &self.session,
-            &views,
-            &self.interaction,
-            &xr_frame_state,
-            swapchains,
-        );
+        if !self.session_running {
+            std::thread::sleep(std::time::Duration::from_millis(1000 / 60));
+            return Ok(());
+        }
+
+        let xr_frame_state = self.frame_wait.wait()?;
 
-        self.frame_stream
-            .end(
+        self.frame_stream.begin()?;
+
+        if !xr_frame_state.should_render {
+            self.frame_stream.end(
                 xr_frame_state.predicted_display_time,
                 self.environment_blend_mode,
-                &[&xr::CompositionLayerProjection::new()
-                    .space(&self.interaction.stage)
-                    .views(&[
-                        xr::CompositionLayerProjectionView::new()
-                            .pose(views[0].pose)
-                            .fov(views[0].fov)
-                            .sub_image(
-                                xr::SwapchainSubImage::new()
-                                    .swapchain(&swapchains[0].handle)
-                                    .image_rect(swapchains[0].rect),
-                            ),
-                        xr::CompositionLayerProjectionView::new()
-                            .pose(views[1].pose)
-                            .fov(views[1].fov)
-                            .sub_image(
-                                xr::SwapchainSubImage::new()
-                                    .swapchain(&swapchains[1].handle)
-                                    .image_rect(swapchains[1].rect),
-                            ),
-                    ])],
-            )
-            .unwrap();
+                &[],
+            )?;
+            return Ok(());
+        }
+
+        if self.swapchains.is_none() {
+            self.swapchains = Some(self.create_swapchains()?);
+        }
+        let swapchains = self.swapchains.as_mut().unwrap();
+
+        self.interaction.sync(&self.session)?;
+
+        let (_flags, views) = self.session.locate_views(
+            VIEW_TYPE,
+            xr_frame_state.predicted_display_time,
+            &self.interaction.stage,
+        )?;
+
+        let mut head_pose = pose_transform_matrix(views[0].pose);
+        head_pose.w_axis = (head_pose.w_axis + pose_transform_matrix(views[1].pose).w_axis) / 2.0;
+
+        let display_time = xr_frame_state.predicted_display_time;
+        let mut input = self
+            .interaction
+            .input_state(&self.session, &xr_frame_state)?;
+        input.head = Some(head_pose);
+        input.dt = self.last_display_time.map_or(0.0, |last| {
+            (display_time.as_nanos() - last.as_nanos()) as f32 / 1e9
+        });
+        self.last_display_time = Some(display_time);
+        self.gestures.apply(&mut input);
+        let mut app = app.borrow_mut();
+        input.transform(app.world_from_stage());
+        app.update(&input);
+        for pulse in app.take_haptics() {
+            self.interaction.apply_haptic(&self.session, &pulse)?;
+        }
+        // views are placed with the stage as it is after this update
+        let world_from_stage = app.world_from_stage();
+        self.head_pose = Some(world_from_stage * head_pose);
+
+        let swapchain_framebuffer = *self.swapchain_framebuffer.get_or_insert_with(|| {
+            unsafe { gl.create_framebuffer() }.expect("Cannot create framebuffer")
+        });
+
+        // swapchain index and array layer each view renders into
+        let multiview = self.multiview;
+        let targets = (0..views.len())
+            .map(|i| {
+                if multiview.is_some() {
+                    (0, i as u32)
+                } else {
+                    (i, 0)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut attachments = Vec::with_capacity(swapchains.len());
+        for swapchain in swapchains.iter_mut() {
+            let image_id = swapchain.handle.acquire_image()?;
+            swapchain.handle.wait_image(xr::Duration::INFINITE)?;
+            let color: glow::Texture =
+                unsafe { std::mem::transmute(swapchain.images[image_id as usize]) };
+
+            let depth = match &mut swapchain.depth {
+                Depth::Swapchain {
+                    handle,
+                    images,
+                    format,
+                } => {
+                    let depth_id = handle.acquire_image()?;
+                    handle.wait_image(xr::Duration::INFINITE)?;
+                    let texture: glow::Texture =
+                        unsafe { std::mem::transmute(images[depth_id as usize]) };
+                    (Depth::attachment(*format), texture)
+                }
+                Depth::Texture(texture) => {
+                    let texture = *texture.get_or_insert_with(|| unsafe {
+                        create_depth_texture(gl, swapchain.rect.extent, swapchain.layers)
+                    });
+                    (glow::DEPTH_ATTACHMENT, texture)
+                }
+            };
+            attachments.push((color, depth));
+
+            if swapchain.samples > 1 && swapchain.msaa.is_none() {
+                swapchain.msaa = Some(unsafe {
+                    create_msaa(
+                        gl,
+                        swapchain.rect.extent,
+                        swapchain.samples,
+                        swapchain.depth.format(),
+                    )
+                });
+            }
+        }
+
+        let frame_views = views
+            .iter()
+            .zip(targets.iter())
+            .map(|(view, (index, _))| {
+                let rect = swapchains[*index].rect;
+                let pose = world_from_stage * pose_transform_matrix(view.pose);
+                FrameView {
+                    pose,
+                    view: View {
+                        viewport: Viewport {
+                            x: rect.offset.x,
+                            y: rect.offset.y,
+                            width: rect.extent.width,
+                            height: rect.extent.height,
+                        },
+                        v_mat: pose.inverse(),
+                        p_mat: fov_perspective_projection_matrix(view.fov, Z_NEAR, Z_FAR),
+                    },
+                    framebuffer: Some(match &swapchains[*index].msaa {
+                        Some(msaa) => *msaa,
+                        None => swapchain_framebuffer,
+                    }),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
+
+            let drawn = match multiview {
+                Some(framebuffer_texture_multiview) => {
+                    let (color, (depth_attachment, depth)) = attachments[0];
+                    let num_views = swapchains[0].layers as i32;
+                    framebuffer_texture_multiview(
+                        glow::FRAMEBUFFER,
+                        glow::COLOR_ATTACHMENT0,
+                        std::mem::transmute::<glow::Texture, u32>(color),
+                        0,
+                        0,
+                        num_views,
+                    );
+                    framebuffer_texture_multiview(
+                        glow::FRAMEBUFFER,
+                        depth_attachment,
+                        std::mem::transmute::<glow::Texture, u32>(depth),
+                        0,
+                        0,
+                        num_views,
+                    );
+                    app.draw_multiview(gl, &frame_views)
+                }
+                None => false,
+            };
+
+            if !drawn {
+                for (frame_view, (index, layer)) in frame_views.iter().zip(targets.iter()) {
+                    let (color, (depth_attachment, depth)) = attachments[*index];
+                    let layers = swapchains[*index].layers;
+                    if let Some(msaa) = swapchains[*index].msaa {
+                        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(msaa));
+                        app.draw_view(gl, frame_view);
+                        gl.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
+                    }
+                    attach_texture(gl, glow::COLOR_ATTACHMENT0, color, layers, *layer);
+                    attach_texture(gl, depth_attachment, depth, layers, *layer);
+                    match swapchains[*index].msaa {
+                        Some(msaa) => {
+                            // resolve, depth too when the compositor reads it
+                            let mut mask = glow::COLOR_BUFFER_BIT;
+                            if self.depth_layer {
+                                if let Depth::Swapchain { .. } = swapchains[*index].depth {
+                                    mask |= glow::DEPTH_BUFFER_BIT;
+                                }
+                            }
+                            let extent = swapchains[*index].rect.extent;
+                            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(msaa));
+                            gl.blit_framebuffer(
+                                0,
+                                0,
+                                extent.width,
+                                extent.height,
+                                0,
+                                0,
+                                extent.width,
+                                extent.height,
+                                mask,
+                                glow::NEAREST,
+                            );
+                            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
+                        }
+                        None => app.draw_view(gl, frame_view),
+                    }
+                }
+            }
+
+            if let Some((mode, window)) = self.mirror {
+                // depth may be attached layered, which would leave the
+                // framebuffer incomplete next to a single color layer
+                gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    glow::DEPTH_STENCIL_ATTACHMENT,
+                    glow::TEXTURE_2D,
+                    None,
+                    0,
+                );
+                for (i, (index, layer)) in targets.iter().enumerate() {
+                    let swapchain = &swapchains[*index];
+                    let rect = swapchain.rect;
+                    let dst = match mode.eye_rect(i, rect.extent.width, rect.extent.height, window)
+                    {
+                        Some(dst) => dst,
+                        None => continue,
+                    };
+                    let (color, _) = attachments[*index];
+                    attach_texture(gl, glow::COLOR_ATTACHMENT0, color, swapchain.layers, *layer);
+                    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
+                    gl.blit_framebuffer(
+                        rect.offset.x,
+                        rect.offset.y,
+                        rect.offset.x + rect.extent.width,
+                        rect.offset.y + rect.extent.height,
+                        dst.x,
+                        dst.y,
+                        dst.x + dst.width,
+                        dst.y + dst.height,
+                        glow::COLOR_BUFFER_BIT,
+                        glow::LINEAR,
+                    );
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
+                }
+            }
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+        }
+
+        for swapchain in swapchains.iter_mut() {
+            swapchain.handle.release_image()?;
+            if let Depth::Swapchain { handle, .. } = &mut swapchain.depth {
+                handle.release_image()?;
+            }
+        }
+
+        // depth info structs are chained into the projection views and must
+        // outlive the end call
+        let depth_infos = targets
+            .iter()
+            .map(|(index, layer)| {
+                let swapchain = &swapchains[*index];
+                match &swapchain.depth {
+                    Depth::Swapchain { handle, .. } if self.depth_layer => {
+                        Some(xr::sys::CompositionLayerDepthInfoKHR {
+                            ty: xr::sys::CompositionLayerDepthInfoKHR::TYPE,
+                            next: std::ptr::null(),
+                            sub_image: xr::sys::SwapchainSubImage {
+                                swapchain: handle.as_raw(),
+                                image_rect: swapchain.rect,
+                                image_array_index: *layer,
+                            },
+                            min_depth: 0.0,
+                            max_depth: 1.0,
+                            near_z: Z_NEAR,
+                            far_z: Z_FAR,
+                        })
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        let projection_views = views
+            .iter()
+            .zip(targets.iter())
+            .zip(depth_infos.iter())
+            .map(|((view, (index, layer)), depth_info)| {
+                let swapchain = &swapchains[*index];
+                let projection_view = xr::CompositionLayerProjectionView::new()
+                    .pose(view.pose)
+                    .fov(view.fov)
+                    .sub_image(
+                        xr::SwapchainSubImage::new()
+                            .swapchain(&swapchain.handle)
+                            .image_rect(swapchain.rect)
+                            .image_array_index(*layer),
+                    );
+                match depth_info {
+                    Some(depth_info) => unsafe {
+                        let mut raw = projection_view.into_raw();
+                        raw.next = depth_info as *const _ as *const _;
+                        xr::CompositionLayerProjectionView::from_raw(raw)
+                    },
+                    None => projection_view,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.frame_stream.end(
+            xr_frame_state.predicted_display_time,
+            self.environment_blend_mode,
+            &[&xr::CompositionLayerProjection::new()
+                .space(&self.interaction.stage)
+                .views(&projection_views)],
+        )?;
+
+        Ok(())
+    }
+
+    /// Whether frames are currently being presented to the headset.
+    pub fn is_running(&self) -> bool {
+        self.session_running && self.app.is_some()
+    }
+
+    /// Head pose of the last rendered frame, midway between the eyes.
+    pub fn head_pose(&self) -> Option<glam::f32::Mat4> {
+        self.head_pose
+    }
+
+    /// Pumps events and renders a frame. `Ok(false)` means the runtime asked
+    /// the application to exit.
+    pub fn try_poll(&mut self) -> Result<bool, XrError> {
+        self.process_events()?;
+        self.wait_frame()?;
+        Ok(!self.exit)
+    }
+}
+
+impl XrBackend<glow::Context> for OpenXR {
+    fn start(&mut self, gl: Rc<glow::Context>, app: SharedApp<glow::Context>) {
+        if self.multiview.is_some() && !gl.supported_extensions().contains("GL_OVR_multiview2") {
+            println!("GL_OVR_multiview2 unsupported, rendering each eye separately");
+            self.multiview = None;
+        }
+        self.app = Some((gl, app));
+        if let Err(e) = self.report_play_area() {
+            eprintln!("XR: {}", e);
+        }
+    }
+
+    fn stop(&mut self) {
+        self.app = None;
+        if self.session_running {
+            if let Err(e) = self.session.request_exit() {
+                eprintln!("XR: {}", XrError::from(e));
+            }
+        }
+    }
+
+    fn poll(&mut self) -> bool {
+        self.try_poll().unwrap_or_else(|e| {
+            eprintln!("XR: {}", e);
+            false
+        })
+    }
+}
+
+/// Creates the first space of `preferences` the runtime supports.
+/// `LocalFloor` is skipped: it needs `XR_EXT_local_floor`, which these
+/// bindings predate.
+fn create_reference_space(
+    session: &xr::Session<xr::OpenGL>,
+    preferences: &[ReferenceSpace],
+) -> Result<(ReferenceSpace, xr::Space), XrError> {
+    let supported = session.enumerate_reference_spaces()?;
+    for &reference_space in preferences {
+        let space_type = match space_type(reference_space) {
+            Some(space_type) if supported.contains(&space_type) => space_type,
+            _ => continue,
+        };
+        let space = session.create_reference_space(space_type, xr::Posef::IDENTITY)?;
+        return Ok((reference_space, space));
+    }
+    Err(XrError::ReferenceSpaceUnavailable)
+}
+
+fn space_type(reference_space: ReferenceSpace) -> Option<xr::ReferenceSpaceType> {
+    match reference_space {
+        ReferenceSpace::BoundedFloor => Some(xr::ReferenceSpaceType::STAGE),
+        ReferenceSpace::LocalFloor => None,
+        ReferenceSpace::Local => Some(xr::ReferenceSpaceType::LOCAL),
+        ReferenceSpace::Viewer => Some(xr::ReferenceSpaceType::VIEW),
+    }
+}
+
+/// Attaches a 2D texture, or one layer of an array texture.
+unsafe fn attach_texture(
+    gl: &glow::Context,
+    attachment: u32,
+    texture: glow::Texture,
+    layers: u32,
+    layer: u32,
+) {
+    if layers > 1 {
+        gl.framebuffer_texture_layer(glow::FRAMEBUFFER, attachment, Some(texture), 0, layer as _);
+    } else {
+        gl.framebuffer_texture_2d(
+            glow::FRAMEBUFFER,
+            attachment,
+            glow::TEXTURE_2D,
+            Some(texture),
+            0,
+        );
+    }
+}
+
+unsafe fn create_msaa(
+    gl: &glow::Context,
+    extent: xr::Extent2Di,
+    samples: u32,
+    depth_format: u32,
+) -> glow::Framebuffer {
+    let create_renderbuffer = |format| {
+        let renderbuffer = gl
+            .create_renderbuffer()
+            .expect("Cannot create renderbuffer");
+        gl.bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+        gl.renderbuffer_storage_multisample(
+            glow::RENDERBUFFER,
+            samples as _,
+            format,
+            extent.width,
+            extent.height,
+        );
+        renderbuffer
+    };
+    let color = create_renderbuffer(glow::SRGB8_ALPHA8);
+    let depth = create_renderbuffer(depth_format);
+    gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+    let framebuffer = gl.create_framebuffer().expect("Cannot create framebuffer");
+    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+    gl.framebuffer_renderbuffer(
+        glow::FRAMEBUFFER,
+        glow::COLOR_ATTACHMENT0,
+        glow::RENDERBUFFER,
+        Some(color),
+    );
+    gl.framebuffer_renderbuffer(
+        glow::FRAMEBUFFER,
+        Depth::attachment(depth_format),
+        glow::RENDERBUFFER,
+        Some(depth),
+    );
+    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+    framebuffer
+}
+
+unsafe fn create_depth_texture(
+    gl: &glow::Context,
+    extent: xr::Extent2Di,
+    layers: u32,
+) -> glow::Texture {
+    let texture = gl.create_texture().expect("Cannot create texture");
+    if layers > 1 {
+        gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(texture));
+        gl.tex_image_3d(
+            glow::TEXTURE_2D_ARRAY,
+            0,
+            glow::DEPTH_COMPONENT24 as _,
+            extent.width,
+            extent.height,
+            layers as _,
+            0,
+            glow::DEPTH_COMPONENT,
+            glow::UNSIGNED_INT,
+            None,
+        );
+        gl.bind_texture(glow::TEXTURE_2D_ARRAY, None);
+    } else {
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        gl.tex_image_2d(
+            glow::TEXTURE_2D,
+            0,
+            glow::DEPTH_COMPONENT24 as _,
+            extent.width,
+            extent.height,
+            0,
+            glow::DEPTH_COMPONENT,
+            glow::UNSIGNED_INT,
+            None,
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+    }
+    texture
+}
+
+/// `None` unless every joint is located.
+fn hand_skeleton(locations: &xr::HandJointLocations) -> Option<HandSkeleton> {
+    let valid = xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID;
+    if !locations
+        .iter()
+        .all(|location| location.location_flags.contains(valid))
+    {
+        return None;
     }
+    Some(HandSkeleton {
+        joints: locations.map(|location| Joint {
+            pose: pose_transform_matrix(location.pose),
+            radius: location.radius,
+        }),
+    })
 }
 
 pub fn pose_transform_matrix(pose: xr::Posef) -> glam::f32::Mat4 {
@@ -0,0 +1,209 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glam::f32::{Mat4, Quat, Vec2, Vec3};
+use glow::HasContext;
+
+use crate::gesture::GestureEvent;
+use crate::hand::HandSkeleton;
+use crate::scene::View;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Button {
+    pub pressed: bool,
+    /// Whether `pressed` differs from the previous frame.
+    pub changed: bool,
+}
+impl Button {
+    pub fn new(pressed: bool, previous: Button) -> Button {
+        Button {
+            pressed,
+            changed: pressed != previous.pressed,
+        }
+    }
+
+    pub fn just_pressed(&self) -> bool {
+        self.pressed && self.changed
+    }
+
+    pub fn just_released(&self) -> bool {
+        !self.pressed && self.changed
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ControllerState {
+    /// World-from-grip transform, `None` while the controller is not tracked.
+    pub grip: Option<Mat4>,
+    /// World-from-aim transform pointing down -Z, `None` while not tracked.
+    pub aim: Option<Mat4>,
+    pub select: Button,
+    pub menu: Button,
+    /// A on the right controller, X on the left.
+    pub primary: Button,
+    /// B on the right controller, Y on the left.
+    pub secondary: Button,
+    /// 0.0 released to 1.0 fully pulled.
+    pub trigger: f32,
+    pub squeeze: f32,
+    /// +X right, +Y forward, both in -1.0..=1.0.
+    pub thumbstick: Vec2,
+    /// Tracked hand joints, `None` without hand tracking or while the hand
+    /// is not seen.
+    pub skeleton: Option<HandSkeleton>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    pub left: ControllerState,
+    pub right: ControllerState,
+    /// Hand gestures that started or ended this frame.
+    pub gestures: Vec<(Hand, GestureEvent)>,
+    /// World-from-head transform, midway between the eyes.
+    pub head: Option<Mat4>,
+    /// Seconds since the previous frame.
+    pub dt: f32,
+}
+impl InputState {
+    pub fn hand(&self, hand: Hand) -> &ControllerState {
+        match hand {
+            Hand::Left => &self.left,
+            Hand::Right => &self.right,
+        }
+    }
+
+    /// Applies `m` to every pose, e.g. to go from stage to world space.
+    pub fn transform(&mut self, m: Mat4) {
+        self.head = self.head.map(|head| m * head);
+        for controller in [&mut self.left, &mut self.right] {
+            controller.grip = controller.grip.map(|grip| m * grip);
+            controller.aim = controller.aim.map(|aim| m * aim);
+            if let Some(skeleton) = &mut controller.skeleton {
+                for joint in &mut skeleton.joints {
+                    joint.pose = m * joint.pose;
+                }
+            }
+        }
+    }
+}
+
+/// Space the backend tracks poses in, named after the WebXR types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceSpace {
+    /// Floor at y = 0 with the play area bounds known; OpenXR's `STAGE`.
+    BoundedFloor,
+    /// Floor at y = 0 around where the session started.
+    LocalFloor,
+    /// Origin at the head's starting position, for seated experiences.
+    Local,
+    /// Follows the head, always available.
+    Viewer,
+}
+impl ReferenceSpace {
+    /// Preference order for standing, room-scale experiences.
+    pub const ROOM_SCALE: [ReferenceSpace; 4] = [
+        ReferenceSpace::BoundedFloor,
+        ReferenceSpace::LocalFloor,
+        ReferenceSpace::Local,
+        ReferenceSpace::Viewer,
+    ];
+    /// Preference order for seated experiences.
+    pub const SEATED: [ReferenceSpace; 2] = [ReferenceSpace::Local, ReferenceSpace::Viewer];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HapticPulse {
+    pub hand: Hand,
+    /// 0.0 to 1.0.
+    pub amplitude: f32,
+    /// Seconds.
+    pub duration: f32,
+    /// Hertz, `None` lets the runtime pick.
+    pub frequency: Option<f32>,
+}
+
+/// One view of a frame. The view's framebuffer is already bound when the app sees it.
+pub struct FrameView<GL: HasContext> {
+    /// World-from-eye transform.
+    pub pose: Mat4,
+    pub view: View,
+    /// `None` for the default framebuffer, and for targets glow has no handle
+    /// for (the WebXR layer framebuffer).
+    pub framebuffer: Option<GL::Framebuffer>,
+}
+
+/// Application side of the frame loop, driven by an [`XrBackend`].
+pub trait XrApp<GL: HasContext> {
+    /// Called once per frame, before any view is rendered.
+    fn update(&mut self, input: &InputState);
+
+    /// Called once per view.
+    unsafe fn draw_view(&mut self, gl: &GL, view: &FrameView<GL>);
+
+    /// Called instead of [`XrApp::draw_view`] when the backend renders all
+    /// views in one pass into a multiview framebuffer (`GL_OVR_multiview2`).
+    /// Returning `false` makes the backend fall back to one `draw_view` per view.
+    unsafe fn draw_multiview(&mut self, _gl: &GL, _views: &[FrameView<GL>]) -> bool {
+        false
+    }
+
+    /// Where the stage sits in the world. Backends apply it to the view
+    /// and input poses, so the app can move the user beyond the room.
+    fn world_from_stage(&self) -> Mat4 {
+        Mat4::IDENTITY
+    }
+
+    /// The backend's reference space moved under the app, e.g. because the
+    /// user recentered or redrew the play area. `previous_from_new` is the
+    /// new origin's pose in the old space, `None` if the runtime does not
+    /// know it. Recenters the app asks the backend for are not reported.
+    fn reference_space_changed(&mut self, _previous_from_new: Option<Mat4>) {}
+
+    /// Stage space (x, z) corners of the play area, in order around it.
+    /// Called whenever the backend learns them, empty when they are unknown.
+    fn play_area_changed(&mut self, _polygon: &[Vec2]) {}
+
+    /// Haptic pulses requested since the last call, drained by the backend
+    /// once per frame after [`XrApp::update`].
+    fn take_haptics(&mut self) -> Vec<HapticPulse> {
+        Vec::new()
+    }
+}
+
+pub type SharedApp<GL> = Rc<RefCell<dyn XrApp<GL>>>;
+
+pub trait XrBackend<GL: HasContext> {
+    /// Starts presenting; `app` is driven every frame until [`XrBackend::stop`].
+    fn start(&mut self, gl: Rc<GL>, app: SharedApp<GL>);
+
+    fn stop(&mut self);
+
+    /// Pumps backend events and, on runtimes that are polled rather than
+    /// callback-driven, runs the next frame. Returns `false` once the
+    /// application should exit.
+    fn poll(&mut self) -> bool;
+}
+
+/// Restricts drawing, clears included, to the view's viewport.
+pub unsafe fn set_viewport<GL: HasContext>(gl: &GL, view: &FrameView<GL>) {
+    let viewport = view.view.viewport;
+    gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+    gl.enable(glow::SCISSOR_TEST);
+    gl.scissor(viewport.x, viewport.y, viewport.width, viewport.height);
+}
+
+/// `head` without pitch and roll, where a recentered space puts its origin.
+pub fn level_pose(head: Mat4) -> Mat4 {
+    let forward = head.transform_vector3(-Vec3::Z);
+    let yaw = (-forward.x).atan2(-forward.z);
+    Mat4::from_rotation_translation(
+        Quat::from_rotation_y(yaw),
+        head.transform_point3(Vec3::ZERO),
+    )
+}
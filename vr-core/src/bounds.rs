@@ -0,0 +1,101 @@
+use glam::f32::{vec2, vec3, Vec2, Vec3};
+
+/// Meters between the lines of a boundary wall, and how high it reaches.
+const WALL_SPACING: f32 = 0.25;
+const WALL_HEIGHT: f32 = 2.5;
+
+/// Edge of the area the runtime's guardian or chaperone marks as safe to
+/// walk in. Each edge of the polygon gets a wall that fades in while the
+/// head or a controller comes close to it.
+#[derive(Debug, Clone)]
+pub struct PlayArea {
+    /// Stage space corners on the floor as (x, z), in order around the
+    /// area. Empty while unknown.
+    polygon: Vec<Vec2>,
+    /// Meters from an edge at which its wall starts to show.
+    pub warning_distance: f32,
+    /// Per edge, from 0.0 (hidden) to 1.0 (touching).
+    fade: Vec<f32>,
+}
+impl Default for PlayArea {
+    fn default() -> PlayArea {
+        PlayArea {
+            polygon: Vec::new(),
+            warning_distance: 0.4,
+            fade: Vec::new(),
+        }
+    }
+}
+impl PlayArea {
+    pub fn polygon(&self) -> &[Vec2] {
+        &self.polygon
+    }
+
+    pub fn set_polygon(&mut self, polygon: &[Vec2]) {
+        self.polygon = polygon.to_vec();
+        self.fade = vec![0.0; polygon.len()];
+    }
+
+    /// Start and end corner of every edge.
+    pub fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let count = self.polygon.len();
+        (0..count).map(move |i| (self.polygon[i], self.polygon[(i + 1) % count]))
+    }
+
+    /// Fades the walls in or out for stage space positions of the head and
+    /// controllers.
+    pub fn update(&mut self, points: &[Vec3]) {
+        let warning_distance = self.warning_distance.max(f32::EPSILON);
+        let fade = self
+            .edges()
+            .map(|(start, end)| {
+                points
+                    .iter()
+                    .map(|point| {
+                        let distance = distance_to_segment(vec2(point.x, point.z), start, end);
+                        (1.0 - distance / warning_distance).clamp(0.0, 1.0)
+                    })
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+        self.fade = fade;
+    }
+
+    /// Stage space segments of the floor outline.
+    pub fn outline(&self) -> impl Iterator<Item = (Vec3, Vec3)> + '_ {
+        self.edges()
+            .map(|(start, end)| (floor(start, 0.0), floor(end, 0.0)))
+    }
+
+    /// Stage space segments of every showing wall's grid, with its fade.
+    pub fn walls(&self) -> impl Iterator<Item = (Vec3, Vec3, f32)> + '_ {
+        self.edges()
+            .zip(self.fade.iter().copied())
+            .filter(|(_, fade)| *fade > 0.0)
+            .flat_map(|((start, end), fade)| {
+                let columns = (start.distance(end) / WALL_SPACING).ceil().max(1.0) as usize;
+                let rows = (WALL_HEIGHT / WALL_SPACING) as usize;
+                let verticals = (0..=columns).map(move |i| {
+                    let corner = start.lerp(end, i as f32 / columns as f32);
+                    (floor(corner, 0.0), floor(corner, WALL_HEIGHT))
+                });
+                let horizontals = (1..=rows).map(move |i| {
+                    let height = i as f32 * WALL_SPACING;
+                    (floor(start, height), floor(end, height))
+                });
+                verticals
+                    .chain(horizontals)
+                    .map(move |(start, end)| (start, end, fade))
+            })
+    }
+}
+
+fn floor(corner: Vec2, height: f32) -> Vec3 {
+    vec3(corner.x, height, corner.y)
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let edge = end - start;
+    let t = ((point - start).dot(edge) / edge.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+    point.distance(start + edge * t)
+}
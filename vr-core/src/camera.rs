@@ -0,0 +1,218 @@
+use glam::f32::{vec2, vec3, Mat4, Quat, Vec2, Vec3};
+
+use crate::backend::{Button, ControllerState, InputState};
+use crate::scene::{View, Viewport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Free-flying desktop camera for running without a headset. The mouse
+/// cursor drives a simulated right controller, the left one is held still.
+/// [`FlyCamera::set_select`] pulls the right controller's trigger.
+#[derive(Debug, Clone)]
+pub struct FlyCamera {
+    pub position: Vec3,
+    /// Radians around +Y, 0 looks down -Z.
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov_y: f32,
+    /// Meters per second.
+    pub speed: f32,
+    /// Radians per pixel of mouse motion.
+    pub sensitivity: f32,
+    moving: [bool; 6],
+    /// Cursor in normalized device coordinates.
+    cursor: Vec2,
+    select_held: bool,
+    select: Button,
+    /// Seconds passed to the last [`FlyCamera::update`].
+    dt: f32,
+}
+impl FlyCamera {
+    pub fn new(position: Vec3, target: Vec3) -> FlyCamera {
+        let dir = (target - position).normalize();
+        FlyCamera {
+            position,
+            yaw: (-dir.x).atan2(-dir.z),
+            pitch: dir.y.asin(),
+            fov_y: std::f32::consts::PI / 2.0,
+            speed: 2.0,
+            sensitivity: 0.003,
+            moving: [false; 6],
+            cursor: Vec2::ZERO,
+            select_held: false,
+            select: Button::default(),
+            dt: 0.0,
+        }
+    }
+
+    pub fn set_moving(&mut self, movement: Movement, pressed: bool) {
+        self.moving[movement as usize] = pressed;
+    }
+
+    pub fn look(&mut self, dx: f32, dy: f32) {
+        let limit = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.yaw -= dx * self.sensitivity;
+        self.pitch = (self.pitch - dy * self.sensitivity).clamp(-limit, limit);
+    }
+
+    /// Cursor position in window pixels, origin at the top left.
+    pub fn set_cursor(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.cursor = vec2(x / width * 2.0 - 1.0, 1.0 - y / height * 2.0);
+    }
+
+    /// Takes effect on the next [`FlyCamera::update`].
+    pub fn set_select(&mut self, pressed: bool) {
+        self.select_held = pressed;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.dt = dt;
+        self.select = Button::new(self.select_held, self.select);
+        let axis = |positive: Movement, negative: Movement| {
+            self.moving[positive as usize] as i32 as f32
+                - self.moving[negative as usize] as i32 as f32
+        };
+        let local = vec3(
+            axis(Movement::Right, Movement::Left),
+            axis(Movement::Up, Movement::Down),
+            axis(Movement::Backward, Movement::Forward),
+        );
+        if local != Vec3::ZERO {
+            self.position += self.rotation() * local.normalize() * self.speed * dt;
+        }
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(self.pitch)
+    }
+
+    /// World-from-camera transform.
+    pub fn pose(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation(), self.position)
+    }
+
+    pub fn view(&self, viewport: Viewport) -> View {
+        let aspect = viewport.width.max(1) as f32 / viewport.height.max(1) as f32;
+        View {
+            viewport,
+            v_mat: self.pose().inverse(),
+            p_mat: Mat4::perspective_rh_gl(self.fov_y, aspect, 0.1, 100.0),
+        }
+    }
+
+    /// Simulated controllers: the right one sits 0.6m along the ray under
+    /// the cursor, pointing along it.
+    pub fn input_state(&self, viewport: Viewport) -> InputState {
+        let aspect = viewport.width.max(1) as f32 / viewport.height.max(1) as f32;
+        let tan = (self.fov_y / 2.0).tan();
+        let ray = vec3(self.cursor.x * tan * aspect, self.cursor.y * tan, -1.0).normalize();
+        let right =
+            Mat4::from_rotation_translation(Quat::from_rotation_arc(-Vec3::Z, ray), ray * 0.6);
+        let left = Mat4::from_translation(vec3(-0.2, -0.2, -0.4));
+        InputState {
+            left: ControllerState {
+                grip: Some(self.pose() * left),
+                aim: Some(self.pose() * left),
+                ..Default::default()
+            },
+            right: ControllerState {
+                grip: Some(self.pose() * right),
+                aim: Some(self.pose() * right),
+                select: self.select,
+                trigger: self.select.pressed as i32 as f32,
+                ..Default::default()
+            },
+            head: Some(self.pose()),
+            dt: self.dt,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWPORT: Viewport = Viewport {
+        x: 0,
+        y: 0,
+        width: 200,
+        height: 100,
+    };
+
+    #[test]
+    fn faces_the_target() {
+        let camera = FlyCamera::new(vec3(0.0, 1.0, 3.0), vec3(0.0, 1.0, 0.0));
+        let forward = camera.pose().transform_vector3(-Vec3::Z);
+        assert!(forward.abs_diff_eq(-Vec3::Z, 1e-6));
+        let camera = FlyCamera::new(Vec3::ZERO, vec3(1.0, 0.0, 0.0));
+        let forward = camera.pose().transform_vector3(-Vec3::Z);
+        assert!(forward.abs_diff_eq(Vec3::X, 1e-6));
+    }
+
+    #[test]
+    fn moves_where_it_faces_at_its_speed() {
+        let mut camera = FlyCamera::new(Vec3::ZERO, vec3(1.0, 0.0, 0.0));
+        camera.set_moving(Movement::Forward, true);
+        camera.update(0.5);
+        assert!(camera.position.abs_diff_eq(vec3(1.0, 0.0, 0.0), 1e-6));
+
+        // diagonals are no faster
+        camera.set_moving(Movement::Left, true);
+        camera.update(0.5);
+        assert!(((camera.position - vec3(1.0, 0.0, 0.0)).length() - 1.0).abs() < 1e-5);
+
+        camera.set_moving(Movement::Forward, false);
+        camera.set_moving(Movement::Left, false);
+        let before = camera.position;
+        camera.update(0.5);
+        assert_eq!(camera.position, before);
+    }
+
+    #[test]
+    fn pitch_stops_short_of_straight_up() {
+        let mut camera = FlyCamera::new(Vec3::ZERO, -Vec3::Z);
+        camera.look(0.0, -1e4);
+        assert!(camera.pitch < std::f32::consts::FRAC_PI_2);
+        assert!(camera.pose().is_finite());
+    }
+
+    #[test]
+    fn input_state_carries_frame_time_and_select() {
+        let mut camera = FlyCamera::new(Vec3::ZERO, -Vec3::Z);
+        camera.set_select(true);
+        camera.update(0.016);
+        let input = camera.input_state(VIEWPORT);
+        assert_eq!(input.dt, 0.016);
+        assert!(input.right.select.just_pressed());
+        assert_eq!(input.right.trigger, 1.0);
+        camera.update(0.016);
+        assert!(!camera.input_state(VIEWPORT).right.select.changed);
+    }
+
+    #[test]
+    fn right_controller_points_under_the_cursor() {
+        let mut camera = FlyCamera::new(Vec3::ZERO, -Vec3::Z);
+        camera.set_cursor(100.0, 50.0, 200.0, 100.0);
+        let aim = camera.input_state(VIEWPORT).right.aim.unwrap();
+        assert!(aim
+            .transform_point3(Vec3::ZERO)
+            .abs_diff_eq(vec3(0.0, 0.0, -0.6), 1e-6));
+
+        // the right edge of a 2:1 window is twice as far out as the top
+        camera.set_cursor(200.0, 50.0, 200.0, 100.0);
+        let aim = camera.input_state(VIEWPORT).right.aim.unwrap();
+        let direction = aim.transform_vector3(-Vec3::Z);
+        let tan = (camera.fov_y / 2.0).tan();
+        assert!((direction.x / -direction.z - 2.0 * tan).abs() < 1e-5);
+        assert!(direction.y.abs() < 1e-6);
+    }
+}
@@ -0,0 +1,289 @@
+use glam::f32::Mat4;
+
+use crate::backend::{Hand, InputState};
+use crate::grab::Collider;
+use crate::graph::{EntityId, SceneGraph};
+use crate::material::Material;
+
+/// What an entity draws at its world transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderable {
+    /// The scene's flat-shaded triangle.
+    Triangle,
+    /// Index into [`Scene::models`](crate::Scene::models) and into that
+    /// model's meshes.
+    Mesh { model: usize, mesh: usize },
+}
+
+/// Tracked pose that drives an entity's local transform. Give such
+/// entities no parent, tracked poses are in world space. They stay hidden
+/// until first tracked and keep their last pose when tracking is lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackedDevice {
+    Head,
+    Grip(Hand),
+    Aim(Hand),
+}
+impl TrackedDevice {
+    pub fn pose(self, input: &InputState) -> Option<Mat4> {
+        match self {
+            TrackedDevice::Head => input.head,
+            TrackedDevice::Grip(hand) => input.hand(hand).grip,
+            TrackedDevice::Aim(hand) => input.hand(hand).aim,
+        }
+    }
+}
+
+/// Per-entity behavior, run once a frame after tracking is applied.
+pub type ScriptFn = dyn FnMut(EntityId, &mut World, &InputState);
+
+/// Empty while it runs, so the world can tell whether it removed or
+/// replaced itself meanwhile.
+pub struct Script(Option<Box<ScriptFn>>);
+impl Script {
+    pub fn new(script: impl FnMut(EntityId, &mut World, &InputState) + 'static) -> Script {
+        Script(Some(Box::new(script)))
+    }
+}
+
+/// App logic that runs once a frame over the whole world.
+pub trait System {
+    fn run(&mut self, world: &mut World, input: &InputState);
+}
+
+/// One component per entity at most, stored by [`EntityId`].
+#[derive(Debug, Clone)]
+pub struct Components<T> {
+    items: Vec<Option<T>>,
+}
+impl<T> Default for Components<T> {
+    fn default() -> Components<T> {
+        Components { items: Vec::new() }
+    }
+}
+impl<T> Components<T> {
+    /// Returns the component the entity had before.
+    pub fn insert(&mut self, id: EntityId, component: T) -> Option<T> {
+        let index = id.index();
+        if index >= self.items.len() {
+            self.items.resize_with(index + 1, || None);
+        }
+        self.items[index].replace(component)
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        self.items.get_mut(id.index())?.take()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.items.get(id.index())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.items.get_mut(id.index())?.as_mut()
+    }
+
+    /// Entities with the component, in spawn order.
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| Some((EntityId::from_index(i), item.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.items
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, item)| Some((EntityId::from_index(i), item.as_mut()?)))
+    }
+}
+
+/// Entities of the scene graph, whose local and world matrices are their
+/// transform component, plus every other component kind. Each frame
+/// [`World::update`] applies tracking, then runs the scripts and systems, so
+/// app behavior lives here rather than in the XR plumbing.
+#[derive(Default)]
+pub struct World {
+    pub graph: SceneGraph,
+    pub renderables: Components<Renderable>,
+    /// Replaces the materials of the entity's mesh, texture indices refer
+    /// to that mesh's model. Triangles only use the base color and are
+    /// white without one.
+    pub materials: Components<Material>,
+    /// Model space shapes the hands can grab the entity by.
+    pub colliders: Components<Collider>,
+    pub tracked: Components<TrackedDevice>,
+    pub scripts: Components<Script>,
+    systems: Vec<Box<dyn System>>,
+}
+impl World {
+    pub fn spawn(&mut self, name: &str, local: Mat4, parent: Option<EntityId>) -> EntityId {
+        self.graph.spawn(name, local, parent)
+    }
+
+    /// Systems run in the order they are added, after the scripts.
+    pub fn add_system(&mut self, system: impl System + 'static) {
+        self.systems.push(Box::new(system));
+    }
+
+    /// Runs one frame: tracking, scripts, systems, then propagates the
+    /// world transforms.
+    pub fn update(&mut self, input: &InputState) {
+        for (id, device) in self.tracked.iter() {
+            if let Some(pose) = device.pose(input) {
+                let entity = &mut self.graph[id];
+                entity.local = pose;
+                entity.visible = true;
+            }
+        }
+        self.graph.propagate();
+
+        // scripts and systems get the whole world, so take each out while it
+        // runs. Scripts removed before their turn are skipped, ones added to
+        // entities that had none first run next frame.
+        let ids = self.scripts.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        for id in ids {
+            let mut script = match self.scripts.get_mut(id).and_then(|script| script.0.take()) {
+                Some(script) => script,
+                None => continue,
+            };
+            script(id, self, input);
+            if let Some(Script(slot @ None)) = self.scripts.get_mut(id) {
+                *slot = Some(script);
+            }
+        }
+
+        let mut systems = std::mem::take(&mut self.systems);
+        for system in &mut systems {
+            system.run(self, input);
+        }
+        systems.append(&mut self.systems);
+        self.systems = systems;
+
+        self.graph.propagate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use glam::f32::{vec3, Vec3};
+
+    use super::*;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Script that logs `name` each time it runs.
+    fn logging(log: &Log, name: &'static str) -> Script {
+        let log = log.clone();
+        Script::new(move |_, _, _| log.borrow_mut().push(name.to_string()))
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        std::mem::take(&mut *log.borrow_mut())
+    }
+
+    #[test]
+    fn tracked_devices_drive_entities() {
+        let mut world = World::default();
+        let head = world.spawn("head", Mat4::IDENTITY, None);
+        world.graph[head].visible = false;
+        world.tracked.insert(head, TrackedDevice::Head);
+        let hat = world.spawn(
+            "hat",
+            Mat4::from_translation(vec3(0.0, 0.2, 0.0)),
+            Some(head),
+        );
+
+        world.update(&InputState::default());
+        assert!(!world.graph[hat].is_world_visible());
+
+        let pose = Mat4::from_translation(vec3(0.0, 1.6, 0.0));
+        let input = InputState {
+            head: Some(pose),
+            ..InputState::default()
+        };
+        world.update(&input);
+        assert_eq!(world.graph[head].world(), pose);
+        assert!(world.graph[hat].is_world_visible());
+        let hat_position = world.graph[hat].world().transform_point3(Vec3::ZERO);
+        assert!(hat_position.abs_diff_eq(vec3(0.0, 1.8, 0.0), 1e-6));
+
+        // lost tracking keeps the last pose
+        world.update(&InputState::default());
+        assert_eq!(world.graph[head].world(), pose);
+    }
+
+    #[test]
+    fn scripts_add_and_remove_scripts() {
+        let log = Log::default();
+        let mut world = World::default();
+        let [first, second, third] =
+            ["first", "second", "third"].map(|name| world.spawn(name, Mat4::IDENTITY, None));
+        world.scripts.insert(second, logging(&log, "second"));
+        world.scripts.insert(third, logging(&log, "third"));
+        let spawned = log.clone();
+        world.scripts.insert(
+            first,
+            Script::new(move |id, world, _| {
+                spawned.borrow_mut().push("first".to_string());
+                // the others are in place while this one runs
+                assert!(world.scripts.get(second).is_some());
+                world.scripts.remove(second);
+                world.scripts.remove(id);
+                let later = world.spawn("later", Mat4::IDENTITY, None);
+                world.scripts.insert(later, logging(&spawned, "later"));
+            }),
+        );
+
+        world.update(&InputState::default());
+        assert_eq!(take(&log), ["first", "third"]);
+        world.update(&InputState::default());
+        assert_eq!(take(&log), ["third", "later"]);
+        assert!(world.scripts.get(first).is_none());
+        assert!(world.scripts.get(second).is_none());
+    }
+
+    #[test]
+    fn script_can_replace_itself() {
+        let log = Log::default();
+        let mut world = World::default();
+        let id = world.spawn("", Mat4::IDENTITY, None);
+        let replacement = log.clone();
+        world.scripts.insert(
+            id,
+            Script::new(move |id, world, _| {
+                world
+                    .scripts
+                    .insert(id, logging(&replacement, "replacement"));
+            }),
+        );
+        world.update(&InputState::default());
+        world.update(&InputState::default());
+        world.update(&InputState::default());
+        assert_eq!(take(&log), ["replacement", "replacement"]);
+    }
+
+    struct Logging(Log, &'static str);
+    impl System for Logging {
+        fn run(&mut self, _: &mut World, _: &InputState) {
+            self.0.borrow_mut().push(self.1.to_string());
+        }
+    }
+
+    #[test]
+    fn systems_run_in_order_after_scripts() {
+        let log = Log::default();
+        let mut world = World::default();
+        world.add_system(Logging(log.clone(), "first system"));
+        world.add_system(Logging(log.clone(), "second system"));
+        let id = world.spawn("", Mat4::IDENTITY, None);
+        world.scripts.insert(id, logging(&log, "script"));
+
+        world.update(&InputState::default());
+        assert_eq!(take(&log), ["script", "first system", "second system"]);
+    }
+}
@@ -0,0 +1,323 @@
+use glam::f32::Vec3;
+
+use crate::backend::{Button, ControllerState, Hand, InputState};
+use crate::hand::{HandJoint, HandSkeleton};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gesture {
+    /// Thumb and index fingertips touching.
+    Pinch,
+    /// Middle, ring and little fingers curled into the palm, and not
+    /// pointing, so a point never grabs.
+    Grab,
+    /// Index finger extended, the others curled.
+    Point,
+    /// Palm facing up.
+    PalmUp,
+}
+impl Gesture {
+    pub const ALL: [Gesture; 4] = [
+        Gesture::Pinch,
+        Gesture::Grab,
+        Gesture::Point,
+        Gesture::PalmUp,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GestureEvent {
+    Started(Gesture),
+    Ended(Gesture),
+}
+
+/// A gesture starts when its measure crosses `start` and only ends once it
+/// crosses back past `end`, so it does not flicker around one value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub start: f32,
+    pub end: f32,
+}
+impl Threshold {
+    /// For measures that start the gesture by dropping below `start`.
+    fn below(&self, active: bool, value: f32) -> bool {
+        value < if active { self.end } else { self.start }
+    }
+
+    /// For measures that start the gesture by rising above `start`.
+    fn above(&self, active: bool, value: f32) -> bool {
+        value > if active { self.end } else { self.start }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureConfig {
+    /// Meters between the thumb and index fingertips.
+    pub pinch: Threshold,
+    /// Mean meters from the middle, ring and little fingertips to the palm.
+    pub grab: Threshold,
+    /// Meters from the index fingertip to the palm, with the other fingers
+    /// curled as for `grab`.
+    pub point: Threshold,
+    /// Cosine between the palm normal and world up.
+    pub palm_up: Threshold,
+}
+impl Default for GestureConfig {
+    fn default() -> GestureConfig {
+        GestureConfig {
+            pinch: Threshold {
+                start: 0.015,
+                end: 0.03,
+            },
+            grab: Threshold {
+                start: 0.06,
+                end: 0.075,
+            },
+            point: Threshold {
+                start: 0.09,
+                end: 0.08,
+            },
+            palm_up: Threshold {
+                start: 0.7,
+                end: 0.5,
+            },
+        }
+    }
+}
+
+/// Tracks the gestures of one hand across frames.
+#[derive(Debug, Clone, Default)]
+pub struct GestureRecognizer {
+    pub config: GestureConfig,
+    active: [bool; 4],
+}
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig) -> GestureRecognizer {
+        GestureRecognizer {
+            config,
+            active: [false; 4],
+        }
+    }
+
+    pub fn is_active(&self, gesture: Gesture) -> bool {
+        self.active[gesture as usize]
+    }
+
+    /// Feeds one frame of joints. A lost hand ends every active gesture.
+    pub fn update(&mut self, skeleton: Option<&HandSkeleton>) -> Vec<GestureEvent> {
+        let active = match skeleton {
+            Some(skeleton) => self.classify(skeleton),
+            None => [false; 4],
+        };
+        let mut events = Vec::new();
+        for gesture in Gesture::ALL {
+            let (was, is) = (self.active[gesture as usize], active[gesture as usize]);
+            if is && !was {
+                events.push(GestureEvent::Started(gesture));
+            } else if was && !is {
+                events.push(GestureEvent::Ended(gesture));
+            }
+        }
+        self.active = active;
+        events
+    }
+
+    fn classify(&self, skeleton: &HandSkeleton) -> [bool; 4] {
+        let config = &self.config;
+        let palm = skeleton.position(HandJoint::Palm);
+        let to_palm = |joint: HandJoint| skeleton.position(joint).distance(palm);
+
+        let pinch = skeleton
+            .position(HandJoint::ThumbTip)
+            .distance(skeleton.position(HandJoint::IndexTip));
+        let curl = (to_palm(HandJoint::MiddleTip)
+            + to_palm(HandJoint::RingTip)
+            + to_palm(HandJoint::LittleTip))
+            / 3.0;
+        let index = to_palm(HandJoint::IndexTip);
+        // the palm faces down its -Y axis
+        let palm_normal = skeleton
+            .joint(HandJoint::Palm)
+            .pose
+            .transform_vector3(-Vec3::Y)
+            .normalize();
+
+        let point_active = self.is_active(Gesture::Point);
+        let point =
+            config.point.above(point_active, index) && config.grab.below(point_active, curl);
+        [
+            config.pinch.below(self.is_active(Gesture::Pinch), pinch),
+            config.grab.below(self.is_active(Gesture::Grab), curl) && !point,
+            point,
+            config
+                .palm_up
+                .above(self.is_active(Gesture::PalmUp), palm_normal.dot(Vec3::Y)),
+        ]
+    }
+}
+
+/// Recognizes gestures on both tracked hands and merges them into the
+/// controller state: pinching presses select and pulls the trigger,
+/// grabbing presses squeeze, just as the matching controller inputs do.
+#[derive(Debug, Clone, Default)]
+pub struct HandGestures {
+    pub recognizers: [GestureRecognizer; 2],
+    /// Merged select of the previous frame, for `Button::changed`.
+    previous_select: [Button; 2],
+}
+impl HandGestures {
+    pub fn new(config: GestureConfig) -> HandGestures {
+        HandGestures {
+            recognizers: [
+                GestureRecognizer::new(config),
+                GestureRecognizer::new(config),
+            ],
+            previous_select: [Button::default(); 2],
+        }
+    }
+
+    /// Updates both hands from `input`'s skeletons and folds the result
+    /// into `input`.
+    pub fn apply(&mut self, input: &mut InputState) {
+        for hand in [Hand::Left, Hand::Right] {
+            let recognizer = &mut self.recognizers[hand as usize];
+            let state = match hand {
+                Hand::Left => &mut input.left,
+                Hand::Right => &mut input.right,
+            };
+            for event in recognizer.update(state.skeleton.as_ref()) {
+                input.gestures.push((hand, event));
+            }
+            merge(recognizer, state, &mut self.previous_select[hand as usize]);
+        }
+    }
+}
+
+fn merge(
+    recognizer: &GestureRecognizer,
+    state: &mut ControllerState,
+    previous_select: &mut Button,
+) {
+    let pinch = recognizer.is_active(Gesture::Pinch);
+    let grab = recognizer.is_active(Gesture::Grab);
+    state.select = Button::new(state.select.pressed || pinch, *previous_select);
+    *previous_select = state.select;
+    if pinch {
+        state.trigger = 1.0;
+    }
+    if grab {
+        state.squeeze = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use glam::f32::Mat4;
+
+    use super::*;
+    use crate::hand::synthetic_hand;
+
+    fn hand(curl: f32, pinch: f32) -> HandSkeleton {
+        synthetic_hand(Hand::Right, Mat4::IDENTITY, curl, pinch)
+    }
+
+    /// A fist with the index finger straight.
+    fn pointing() -> HandSkeleton {
+        let mut skeleton = hand(1.0, 0.0);
+        let flat = hand(0.0, 0.0);
+        for joint in [
+            HandJoint::IndexMetacarpal,
+            HandJoint::IndexProximal,
+            HandJoint::IndexIntermediate,
+            HandJoint::IndexDistal,
+            HandJoint::IndexTip,
+        ] {
+            skeleton.joints[joint as usize] = flat.joints[joint as usize];
+        }
+        skeleton
+    }
+
+    fn active(skeleton: &HandSkeleton) -> Vec<Gesture> {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.update(Some(skeleton));
+        Gesture::ALL
+            .into_iter()
+            .filter(|&gesture| recognizer.is_active(gesture))
+            .collect()
+    }
+
+    fn input(skeleton: HandSkeleton) -> InputState {
+        let mut input = InputState::default();
+        input.right.skeleton = Some(skeleton);
+        input
+    }
+
+    #[test]
+    fn classifies_synthetic_hands() {
+        assert_eq!(active(&hand(0.0, 0.0)), []);
+        assert_eq!(active(&hand(1.0, 0.0)), [Gesture::Grab]);
+        assert_eq!(active(&hand(0.0, 1.0)), [Gesture::Pinch]);
+        assert_eq!(active(&pointing()), [Gesture::Point]);
+        let palm_up = synthetic_hand(Hand::Right, Mat4::from_rotation_z(PI), 0.0, 0.0);
+        assert_eq!(active(&palm_up), [Gesture::PalmUp]);
+    }
+
+    #[test]
+    fn pointing_does_not_squeeze() {
+        let mut gestures = HandGestures::default();
+        let mut pointing = input(pointing());
+        gestures.apply(&mut pointing);
+        assert_eq!(pointing.right.squeeze, 0.0);
+        assert!(!pointing.right.select.pressed);
+
+        let mut fist = input(hand(1.0, 0.0));
+        gestures.apply(&mut fist);
+        assert_eq!(fist.right.squeeze, 1.0);
+        assert_eq!(
+            fist.gestures,
+            [
+                (Hand::Right, GestureEvent::Started(Gesture::Grab)),
+                (Hand::Right, GestureEvent::Ended(Gesture::Point)),
+            ]
+        );
+    }
+
+    #[test]
+    fn pinch_presses_and_releases_select() {
+        let mut gestures = HandGestures::default();
+        let frames = [0.0, 1.0, 1.0, 0.0].map(|pinch| {
+            let mut input = input(hand(0.0, pinch));
+            gestures.apply(&mut input);
+            input.right
+        });
+        assert!(!frames[0].select.pressed);
+        assert!(frames[1].select.just_pressed());
+        assert_eq!(frames[1].trigger, 1.0);
+        assert!(frames[2].select.pressed && !frames[2].select.changed);
+        assert!(frames[3].select.just_released());
+    }
+
+    #[test]
+    fn thresholds_keep_gestures_from_flickering() {
+        let mut recognizer = GestureRecognizer::default();
+        // tips 0.021m apart: between the pinch start and end distances
+        recognizer.update(Some(&hand(0.0, 0.8)));
+        assert!(!recognizer.is_active(Gesture::Pinch));
+        recognizer.update(Some(&hand(0.0, 1.0)));
+        recognizer.update(Some(&hand(0.0, 0.8)));
+        assert!(recognizer.is_active(Gesture::Pinch));
+        recognizer.update(Some(&hand(0.0, 0.0)));
+        assert!(!recognizer.is_active(Gesture::Pinch));
+    }
+
+    #[test]
+    fn lost_hand_ends_gestures() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.update(Some(&hand(1.0, 0.0)));
+        assert_eq!(
+            recognizer.update(None),
+            [GestureEvent::Ended(Gesture::Grab)]
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use glam::f32::{Mat4, Quat, Vec3};
+
+use crate::backend::{Hand, InputState};
+use crate::graph::EntityId;
+
+/// Squeeze values a grab starts above and ends below.
+const SQUEEZE_START: f32 = 0.5;
+const SQUEEZE_END: f32 = 0.4;
+
+/// Meters the hands have to be apart to rotate and scale an object.
+const MIN_SPAN: f32 = 0.01;
+
+/// Shape, in the object's model space, a controller has to be inside to
+/// grab the object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collider {
+    Sphere { center: Vec3, radius: f32 },
+    Box { center: Vec3, half_extents: Vec3 },
+}
+impl Collider {
+    pub fn contains(&self, point: Vec3) -> bool {
+        match *self {
+            Collider::Sphere { center, radius } => {
+                point.distance_squared(center) <= radius * radius
+            }
+            Collider::Box {
+                center,
+                half_extents,
+            } => (point - center).abs().cmple(half_extents).all(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Grabbable {
+    pub id: EntityId,
+    /// World-from-model transform.
+    pub transform: Mat4,
+    pub collider: Collider,
+}
+impl Grabbable {
+    pub fn contains(&self, point: Vec3) -> bool {
+        let local = self.transform.inverse().transform_point3(point);
+        self.collider.contains(local)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Held {
+    object: EntityId,
+    /// Grip-from-model transform at the time of grabbing.
+    offset: Mat4,
+}
+
+/// Both hands on one object, as they were when the second hand joined or
+/// the hands last parted.
+#[derive(Debug, Clone, Copy)]
+struct TwoHanded {
+    transform: Mat4,
+    midpoint: Vec3,
+    /// Left to right hand.
+    span: Vec3,
+}
+
+/// Squeezing while a grip is inside an object's collider attaches the
+/// object to that hand; releasing leaves it where it is. With both hands
+/// on one object, moving them apart or around each other scales and
+/// rotates it about their midpoint.
+#[derive(Debug, Clone, Default)]
+pub struct GrabSystem {
+    /// Indexed by [`Hand`].
+    held: [Option<Held>; 2],
+    squeezing: [bool; 2],
+    two_handed: Option<TwoHanded>,
+}
+impl GrabSystem {
+    /// Object the hand is holding.
+    pub fn held(&self, hand: Hand) -> Option<EntityId> {
+        self.held[hand as usize].map(|held| held.object)
+    }
+
+    /// Grabs, releases and moves `objects`. Returns the hands that started
+    /// holding something this frame. A hand that loses tracking lets go,
+    /// as does one whose object is no longer among `objects`.
+    pub fn update(&mut self, input: &InputState, objects: &mut [Grabbable]) -> Vec<Hand> {
+        for held in &mut self.held {
+            if held.is_some_and(|held| find(objects, held.object).is_none()) {
+                *held = None;
+                self.two_handed = None;
+            }
+        }
+
+        let mut grabbed = Vec::new();
+        for hand in [Hand::Left, Hand::Right] {
+            let i = hand as usize;
+            let controller = input.hand(hand);
+            self.squeezing[i] = controller.squeeze
+                > if self.squeezing[i] {
+                    SQUEEZE_END
+                } else {
+                    SQUEEZE_START
+                };
+            let grip = match controller.grip {
+                Some(grip) if self.squeezing[i] => grip,
+                _ => {
+                    self.release(hand, input, objects);
+                    continue;
+                }
+            };
+            if self.held[i].is_none() {
+                let grip_position = position(grip);
+                if let Some(object) = objects.iter().find(|o| o.contains(grip_position)) {
+                    self.held[i] = Some(Held {
+                        object: object.id,
+                        offset: grip.inverse() * object.transform,
+                    });
+                    grabbed.push(hand);
+                }
+            }
+        }
+
+        let [left, right] = self.held;
+        let grips = (input.left.grip, input.right.grip);
+        match (left, right, grips) {
+            (Some(l), Some(r), (Some(left_grip), Some(right_grip))) if l.object == r.object => {
+                let (left, right) = (position(left_grip), position(right_grip));
+                let span = right - left;
+                if span.length() < MIN_SPAN {
+                    // hands together give no direction to rotate by: hold
+                    // the object still and start over once they part
+                    self.two_handed = None;
+                    return grabbed;
+                }
+                let object = find(objects, l.object).unwrap();
+                let start = *self.two_handed.get_or_insert(TwoHanded {
+                    transform: object.transform,
+                    midpoint: (left + right) / 2.0,
+                    span,
+                });
+                let scale = span.length() / start.span.length();
+                let rotation = Quat::from_rotation_arc(start.span.normalize(), span.normalize());
+                object.transform = Mat4::from_translation((left + right) / 2.0)
+                    * Mat4::from_scale_rotation_translation(
+                        Vec3::splat(scale),
+                        rotation,
+                        Vec3::ZERO,
+                    )
+                    * Mat4::from_translation(-start.midpoint)
+                    * start.transform;
+            }
+            _ => {
+                for (held, grip) in [(left, grips.0), (right, grips.1)] {
+                    if let (Some(held), Some(grip)) = (held, grip) {
+                        find(objects, held.object).unwrap().transform = grip * held.offset;
+                    }
+                }
+            }
+        }
+        grabbed
+    }
+
+    fn release(&mut self, hand: Hand, input: &InputState, objects: &mut [Grabbable]) {
+        let released = match self.held[hand as usize].take() {
+            Some(released) => released,
+            None => return,
+        };
+        self.two_handed = None;
+        // the other hand keeps holding from where the object is now
+        let other = match hand {
+            Hand::Left => Hand::Right,
+            Hand::Right => Hand::Left,
+        };
+        if let (Some(held), Some(grip)) =
+            (self.held[other as usize].as_mut(), input.hand(other).grip)
+        {
+            if held.object == released.object {
+                held.offset = grip.inverse() * find(objects, released.object).unwrap().transform;
+            }
+        }
+    }
+}
+
+fn find(objects: &mut [Grabbable], id: EntityId) -> Option<&mut Grabbable> {
+    objects.iter_mut().find(|object| object.id == id)
+}
+
+fn position(pose: Mat4) -> Vec3 {
+    pose.transform_point3(Vec3::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::f32::vec3;
+
+    use super::*;
+
+    fn squeezing(left: Option<Vec3>, right: Option<Vec3>) -> InputState {
+        let mut input = InputState::default();
+        for (state, position) in [(&mut input.left, left), (&mut input.right, right)] {
+            state.grip = position.map(Mat4::from_translation);
+            state.squeeze = if position.is_some() { 1.0 } else { 0.0 };
+        }
+        input
+    }
+
+    fn cube(id: usize, position: Vec3) -> Grabbable {
+        Grabbable {
+            id: EntityId::from_index(id),
+            transform: Mat4::from_translation(position),
+            collider: Collider::Box {
+                center: Vec3::ZERO,
+                half_extents: Vec3::splat(0.5),
+            },
+        }
+    }
+
+    #[test]
+    fn one_hand_carries_object() {
+        let mut grab = GrabSystem::default();
+        let mut objects = [cube(0, Vec3::ZERO)];
+        let grabbed = grab.update(&squeezing(None, Some(vec3(0.1, 0.0, 0.0))), &mut objects);
+        assert_eq!(grabbed, [Hand::Right]);
+        assert_eq!(grab.held(Hand::Right), Some(EntityId::from_index(0)));
+        grab.update(&squeezing(None, Some(vec3(1.1, 0.0, 0.0))), &mut objects);
+        assert!(position(objects[0].transform).abs_diff_eq(vec3(1.0, 0.0, 0.0), 1e-6));
+        grab.update(&squeezing(None, None), &mut objects);
+        assert_eq!(grab.held(Hand::Right), None);
+    }
+
+    #[test]
+    fn two_hands_scale_and_rotate() {
+        let mut grab = GrabSystem::default();
+        let mut objects = [cube(0, Vec3::ZERO)];
+        let (left, right) = (vec3(-0.2, 0.0, 0.0), vec3(0.2, 0.0, 0.0));
+        grab.update(&squeezing(Some(left), Some(right)), &mut objects);
+        // twice as far apart, turned a quarter around +Y
+        let (left, right) = (vec3(0.0, 0.0, 0.4), vec3(0.0, 0.0, -0.4));
+        grab.update(&squeezing(Some(left), Some(right)), &mut objects);
+        let expected = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            Vec3::ZERO,
+        );
+        assert!(objects[0].transform.abs_diff_eq(expected, 1e-5));
+    }
+
+    #[test]
+    fn hands_together_keep_transform_finite() {
+        let mut grab = GrabSystem::default();
+        let mut objects = [cube(0, Vec3::ZERO)];
+        let together = Some(vec3(0.1, 0.0, 0.0));
+        grab.update(&squeezing(together, together), &mut objects);
+        grab.update(&squeezing(together, together), &mut objects);
+        assert!(objects[0].transform.is_finite());
+        assert_eq!(objects[0].transform, Mat4::IDENTITY);
+
+        // parting starts the two-handed grab from where the hands are
+        grab.update(
+            &squeezing(Some(vec3(-0.1, 0.0, 0.0)), Some(vec3(0.3, 0.0, 0.0))),
+            &mut objects,
+        );
+        assert_eq!(objects[0].transform, Mat4::IDENTITY);
+        grab.update(
+            &squeezing(Some(vec3(-0.3, 0.0, 0.0)), Some(vec3(0.5, 0.0, 0.0))),
+            &mut objects,
+        );
+        assert!(objects[0].transform.is_finite());
+        assert!((objects[0].transform.x_axis.length() - 2.0).abs() < 1e-5);
+
+        // the remaining hand carries it on from there
+        let before = objects[0].transform;
+        grab.update(&squeezing(None, Some(vec3(0.5, 0.0, 0.0))), &mut objects);
+        assert!(objects[0].transform.abs_diff_eq(before, 1e-6));
+    }
+
+    #[test]
+    fn removed_object_is_let_go() {
+        let mut grab = GrabSystem::default();
+        let right = Some(vec3(2.0, 0.0, 0.0));
+        let mut objects = vec![cube(0, Vec3::ZERO), cube(1, vec3(2.0, 0.0, 0.0))];
+        grab.update(&squeezing(None, right), &mut objects);
+        assert_eq!(grab.held(Hand::Right), Some(EntityId::from_index(1)));
+
+        // an earlier object losing its collider keeps the hold on the same one
+        objects.remove(0);
+        grab.update(&squeezing(None, Some(vec3(3.0, 0.0, 0.0))), &mut objects);
+        assert_eq!(grab.held(Hand::Right), Some(EntityId::from_index(1)));
+        assert!(position(objects[0].transform).abs_diff_eq(vec3(3.0, 0.0, 0.0), 1e-6));
+
+        objects.clear();
+        grab.update(&squeezing(None, Some(vec3(3.0, 0.0, 0.0))), &mut objects);
+        assert_eq!(grab.held(Hand::Right), None);
+    }
+}
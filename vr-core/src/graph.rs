@@ -0,0 +1,295 @@
+use glam::f32::Mat4;
+
+/// Handle to an entity of a [`SceneGraph`], valid for the graph's lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(usize);
+impl EntityId {
+    pub(crate) fn from_index(index: usize) -> EntityId {
+        EntityId(index)
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub name: Option<String>,
+    /// Parent-from-entity transform, or world-from-entity for roots.
+    pub local: Mat4,
+    /// Hides the entity and everything under it.
+    pub visible: bool,
+    parent: Option<EntityId>,
+    children: Vec<EntityId>,
+    world: Mat4,
+    world_visible: bool,
+}
+impl Entity {
+    pub fn parent(&self) -> Option<EntityId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[EntityId] {
+        &self.children
+    }
+
+    /// World-from-entity transform as of the last [`SceneGraph::propagate`].
+    pub fn world(&self) -> Mat4 {
+        self.world
+    }
+
+    /// Whether the entity and all its ancestors are visible, as of the last
+    /// [`SceneGraph::propagate`].
+    pub fn is_world_visible(&self) -> bool {
+        self.world_visible
+    }
+}
+
+/// Entities with local transforms arranged in a parent/child hierarchy.
+/// Edit local transforms freely, then call [`SceneGraph::propagate`] once to
+/// bring every world transform up to date.
+#[derive(Debug, Clone, Default)]
+pub struct SceneGraph {
+    entities: Vec<Entity>,
+}
+impl SceneGraph {
+    pub fn spawn(&mut self, name: &str, local: Mat4, parent: Option<EntityId>) -> EntityId {
+        let id = EntityId(self.entities.len());
+        let world = parent.map_or(Mat4::IDENTITY, |parent| self[parent].world) * local;
+        self.entities.push(Entity {
+            name: (!name.is_empty()).then(|| name.to_string()),
+            local,
+            visible: true,
+            parent: None,
+            children: Vec::new(),
+            world,
+            world_visible: true,
+        });
+        self.set_parent(id, parent, false);
+        id
+    }
+
+    /// Moves `id` under `parent`, or makes it a root. With `keep_world`
+    /// its local transform is adjusted so it stays where it is, as when
+    /// picking something up; otherwise it keeps its local transform.
+    /// Attaching an entity to one of its own descendants is ignored.
+    pub fn set_parent(&mut self, id: EntityId, parent: Option<EntityId>, keep_world: bool) {
+        if parent.is_some_and(|parent| self.is_ancestor(id, parent)) {
+            return;
+        }
+        if let Some(old) = self[id].parent.take() {
+            self.entities[old.0].children.retain(|&child| child != id);
+        }
+        if let Some(parent) = parent {
+            self.entities[parent.0].children.push(id);
+        }
+        self[id].parent = parent;
+        if keep_world {
+            let parent_world = parent.map_or(Mat4::IDENTITY, |parent| self[parent].world);
+            let world = self[id].world;
+            self[id].local = parent_world.inverse() * world;
+        }
+    }
+
+    /// Whether `ancestor` is `id` or above it.
+    pub fn is_ancestor(&self, ancestor: EntityId, id: EntityId) -> bool {
+        let mut current = Some(id);
+        while let Some(entity) = current {
+            if entity == ancestor {
+                return true;
+            }
+            current = self[entity].parent;
+        }
+        false
+    }
+
+    /// Places `id` at `world` by changing its local transform. The world
+    /// transforms of its descendants follow on the next propagation.
+    pub fn set_world(&mut self, id: EntityId, world: Mat4) {
+        let parent_world = self[id]
+            .parent
+            .map_or(Mat4::IDENTITY, |parent| self[parent].world);
+        let entity = &mut self[id];
+        entity.local = parent_world.inverse() * world;
+        entity.world = world;
+    }
+
+    /// Recomputes every world transform and visibility from the roots down.
+    pub fn propagate(&mut self) {
+        let mut stack = (0..self.entities.len())
+            .filter(|&i| self.entities[i].parent.is_none())
+            .map(|i| (EntityId(i), Mat4::IDENTITY, true))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_world, parent_visible)) = stack.pop() {
+            let entity = &mut self.entities[id.0];
+            entity.world = parent_world * entity.local;
+            entity.world_visible = parent_visible && entity.visible;
+            let (world, visible) = (entity.world, entity.world_visible);
+            stack.extend(entity.children.iter().map(|&child| (child, world, visible)));
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.entities
+            .iter()
+            .enumerate()
+            .map(|(i, entity)| (EntityId(i), entity))
+    }
+
+    pub fn find(&self, name: &str) -> Option<EntityId> {
+        self.iter()
+            .find(|(_, entity)| entity.name.as_deref() == Some(name))
+            .map(|(id, _)| id)
+    }
+}
+
+impl std::ops::Index<EntityId> for SceneGraph {
+    type Output = Entity;
+
+    fn index(&self, id: EntityId) -> &Entity {
+        &self.entities[id.0]
+    }
+}
+
+impl std::ops::IndexMut<EntityId> for SceneGraph {
+    fn index_mut(&mut self, id: EntityId) -> &mut Entity {
+        &mut self.entities[id.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::f32::{vec3, Quat, Vec3};
+
+    use super::*;
+
+    fn translation(x: f32, y: f32, z: f32) -> Mat4 {
+        Mat4::from_translation(vec3(x, y, z))
+    }
+
+    fn position(graph: &SceneGraph, id: EntityId) -> Vec3 {
+        graph[id].world().transform_point3(Vec3::ZERO)
+    }
+
+    #[test]
+    fn propagates_through_levels() {
+        let mut graph = SceneGraph::default();
+        let root = graph.spawn("root", translation(1.0, 0.0, 0.0), None);
+        let child = graph.spawn(
+            "child",
+            Mat4::from_rotation_translation(
+                Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+                vec3(0.0, 1.0, 0.0),
+            ),
+            Some(root),
+        );
+        let grandchild = graph.spawn("grandchild", translation(0.0, 0.0, -1.0), Some(child));
+        assert!(position(&graph, grandchild).abs_diff_eq(vec3(0.0, 1.0, 0.0), 1e-5));
+
+        graph[root].local = translation(0.0, 0.0, 2.0);
+        graph.propagate();
+        assert!(position(&graph, child).abs_diff_eq(vec3(0.0, 1.0, 2.0), 1e-5));
+        // -Z turned a quarter to the left is -X
+        assert!(position(&graph, grandchild).abs_diff_eq(vec3(-1.0, 1.0, 2.0), 1e-5));
+        assert_eq!(graph.find("grandchild"), Some(grandchild));
+        assert_eq!(graph[child].children(), [grandchild]);
+    }
+
+    #[test]
+    fn reparenting_can_keep_the_world_transform() {
+        let mut graph = SceneGraph::default();
+        let hand = graph.spawn("hand", translation(0.0, 1.0, 0.0), None);
+        let cube = graph.spawn("cube", translation(2.0, 0.0, 0.0), None);
+
+        graph.set_parent(cube, Some(hand), true);
+        graph.propagate();
+        assert!(position(&graph, cube).abs_diff_eq(vec3(2.0, 0.0, 0.0), 1e-5));
+        assert!(graph[cube]
+            .local
+            .abs_diff_eq(translation(2.0, -1.0, 0.0), 1e-5));
+        assert_eq!(graph[hand].children(), [cube]);
+
+        graph[hand].local = translation(0.0, 2.0, 0.0);
+        graph.propagate();
+        assert!(position(&graph, cube).abs_diff_eq(vec3(2.0, 1.0, 0.0), 1e-5));
+
+        // without keep_world the local transform carries over
+        graph.set_parent(cube, None, false);
+        graph.propagate();
+        assert!(position(&graph, cube).abs_diff_eq(vec3(2.0, -1.0, 0.0), 1e-5));
+        assert_eq!(graph[cube].parent(), None);
+        assert!(graph[hand].children().is_empty());
+    }
+
+    #[test]
+    fn set_world_places_a_child() {
+        let mut graph = SceneGraph::default();
+        let parent = graph.spawn(
+            "parent",
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(2.0),
+                Quat::IDENTITY,
+                vec3(0.0, 0.0, -1.0),
+            ),
+            None,
+        );
+        let child = graph.spawn("child", Mat4::IDENTITY, Some(parent));
+        let grandchild = graph.spawn("grandchild", translation(1.0, 0.0, 0.0), Some(child));
+
+        graph.set_world(child, translation(3.0, 0.0, 0.0));
+        assert!(position(&graph, child).abs_diff_eq(vec3(3.0, 0.0, 0.0), 1e-5));
+        graph.propagate();
+        assert!(position(&graph, child).abs_diff_eq(vec3(3.0, 0.0, 0.0), 1e-5));
+        // in the parent's scaled space
+        let local = Mat4::from_scale_rotation_translation(
+            Vec3::splat(0.5),
+            Quat::IDENTITY,
+            vec3(1.5, 0.0, 0.5),
+        );
+        assert!(graph[child].local.abs_diff_eq(local, 1e-5));
+        assert!(position(&graph, grandchild).abs_diff_eq(vec3(4.0, 0.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn ignores_parenting_cycles() {
+        let mut graph = SceneGraph::default();
+        let root = graph.spawn("root", Mat4::IDENTITY, None);
+        let child = graph.spawn("child", Mat4::IDENTITY, Some(root));
+        let grandchild = graph.spawn("grandchild", Mat4::IDENTITY, Some(child));
+
+        graph.set_parent(root, Some(grandchild), false);
+        graph.set_parent(child, Some(child), false);
+        assert_eq!(graph[root].parent(), None);
+        assert_eq!(graph[child].parent(), Some(root));
+        assert!(graph.is_ancestor(root, grandchild));
+        assert!(!graph.is_ancestor(grandchild, root));
+        graph.propagate();
+        assert_eq!(graph.iter().count(), 3);
+    }
+
+    #[test]
+    fn visibility_is_inherited() {
+        let mut graph = SceneGraph::default();
+        let root = graph.spawn("root", Mat4::IDENTITY, None);
+        let child = graph.spawn("child", Mat4::IDENTITY, Some(root));
+        let grandchild = graph.spawn("grandchild", Mat4::IDENTITY, Some(child));
+        let sibling = graph.spawn("sibling", Mat4::IDENTITY, None);
+
+        graph[child].visible = false;
+        graph.propagate();
+        assert!(graph[root].is_world_visible());
+        assert!(!graph[child].is_world_visible());
+        assert!(!graph[grandchild].is_world_visible());
+        assert!(graph[sibling].is_world_visible());
+
+        graph[child].visible = true;
+        graph[root].visible = false;
+        graph.propagate();
+        assert!(!graph[grandchild].is_world_visible());
+
+        graph[root].visible = true;
+        graph.propagate();
+        assert!(graph[grandchild].is_world_visible());
+    }
+}
@@ -0,0 +1,219 @@
+use std::f32::consts::PI;
+
+use glam::f32::{vec3, Mat4, Quat, Vec3};
+
+use crate::backend::Hand;
+
+pub const HAND_JOINT_COUNT: usize = 26;
+
+/// Hand joints in `XR_EXT_hand_tracking` order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandJoint {
+    Palm,
+    Wrist,
+    ThumbMetacarpal,
+    ThumbProximal,
+    ThumbDistal,
+    ThumbTip,
+    IndexMetacarpal,
+    IndexProximal,
+    IndexIntermediate,
+    IndexDistal,
+    IndexTip,
+    MiddleMetacarpal,
+    MiddleProximal,
+    MiddleIntermediate,
+    MiddleDistal,
+    MiddleTip,
+    RingMetacarpal,
+    RingProximal,
+    RingIntermediate,
+    RingDistal,
+    RingTip,
+    LittleMetacarpal,
+    LittleProximal,
+    LittleIntermediate,
+    LittleDistal,
+    LittleTip,
+}
+
+/// Joint names used by WebXR's `XRHand`, which has no palm joint.
+const WEBXR_NAMES: [&str; HAND_JOINT_COUNT - 1] = [
+    "wrist",
+    "thumb-metacarpal",
+    "thumb-phalanx-proximal",
+    "thumb-phalanx-distal",
+    "thumb-tip",
+    "index-finger-metacarpal",
+    "index-finger-phalanx-proximal",
+    "index-finger-phalanx-intermediate",
+    "index-finger-phalanx-distal",
+    "index-finger-tip",
+    "middle-finger-metacarpal",
+    "middle-finger-phalanx-proximal",
+    "middle-finger-phalanx-intermediate",
+    "middle-finger-phalanx-distal",
+    "middle-finger-tip",
+    "ring-finger-metacarpal",
+    "ring-finger-phalanx-proximal",
+    "ring-finger-phalanx-intermediate",
+    "ring-finger-phalanx-distal",
+    "ring-finger-tip",
+    "pinky-finger-metacarpal",
+    "pinky-finger-phalanx-proximal",
+    "pinky-finger-phalanx-intermediate",
+    "pinky-finger-phalanx-distal",
+    "pinky-finger-tip",
+];
+
+impl HandJoint {
+    pub const ALL: [HandJoint; HAND_JOINT_COUNT] = [
+        HandJoint::Palm,
+        HandJoint::Wrist,
+        HandJoint::ThumbMetacarpal,
+        HandJoint::ThumbProximal,
+        HandJoint::ThumbDistal,
+        HandJoint::ThumbTip,
+        HandJoint::IndexMetacarpal,
+        HandJoint::IndexProximal,
+        HandJoint::IndexIntermediate,
+        HandJoint::IndexDistal,
+        HandJoint::IndexTip,
+        HandJoint::MiddleMetacarpal,
+        HandJoint::MiddleProximal,
+        HandJoint::MiddleIntermediate,
+        HandJoint::MiddleDistal,
+        HandJoint::MiddleTip,
+        HandJoint::RingMetacarpal,
+        HandJoint::RingProximal,
+        HandJoint::RingIntermediate,
+        HandJoint::RingDistal,
+        HandJoint::RingTip,
+        HandJoint::LittleMetacarpal,
+        HandJoint::LittleProximal,
+        HandJoint::LittleIntermediate,
+        HandJoint::LittleDistal,
+        HandJoint::LittleTip,
+    ];
+
+    pub fn webxr_name(self) -> Option<&'static str> {
+        (self as usize).checked_sub(1).map(|i| WEBXR_NAMES[i])
+    }
+
+    /// Joint the bone ending at this one starts from. Metacarpals hang off
+    /// the wrist; the palm and wrist have no parent.
+    pub fn parent(self) -> Option<HandJoint> {
+        match self {
+            HandJoint::Palm | HandJoint::Wrist => None,
+            HandJoint::ThumbMetacarpal
+            | HandJoint::IndexMetacarpal
+            | HandJoint::MiddleMetacarpal
+            | HandJoint::RingMetacarpal
+            | HandJoint::LittleMetacarpal => Some(HandJoint::Wrist),
+            joint => Some(HandJoint::ALL[joint as usize - 1]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Joint {
+    /// World-from-joint transform.
+    pub pose: Mat4,
+    /// Meters from the joint to the skin.
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HandSkeleton {
+    /// Indexed by [`HandJoint`].
+    pub joints: [Joint; HAND_JOINT_COUNT],
+}
+impl HandSkeleton {
+    pub fn joint(&self, joint: HandJoint) -> &Joint {
+        &self.joints[joint as usize]
+    }
+
+    pub fn position(&self, joint: HandJoint) -> Vec3 {
+        self.joint(joint).pose.transform_point3(Vec3::ZERO)
+    }
+
+    /// Start and end of every bone.
+    pub fn bones(&self) -> impl Iterator<Item = (Vec3, Vec3)> + '_ {
+        HandJoint::ALL.into_iter().filter_map(|joint| {
+            let parent = joint.parent()?;
+            Some((self.position(parent), self.position(joint)))
+        })
+    }
+}
+
+/// A plausible hand, for running without hand tracking hardware. `wrist`
+/// is the world-from-wrist transform with the fingers pointing down -Z and
+/// the back of the hand up +Y; `curl` goes from 0.0 (flat) to 1.0 (fist).
+/// `pinch` from 0.0 to 1.0 draws the thumb tip onto the index fingertip.
+pub fn synthetic_hand(hand: Hand, wrist: Mat4, curl: f32, pinch: f32) -> HandSkeleton {
+    // right hand: metacarpal offset, yaw and bone lengths per finger,
+    // thumb first
+    const FINGERS: [([f32; 3], f32, &[f32]); 5] = [
+        ([-0.025, -0.01, -0.01], 0.7, &[0.035, 0.03, 0.025]),
+        ([-0.015, 0.0, -0.01], 0.05, &[0.065, 0.04, 0.025, 0.022]),
+        ([0.0, 0.0, -0.01], 0.0, &[0.065, 0.045, 0.03, 0.024]),
+        ([0.015, 0.0, -0.01], -0.05, &[0.06, 0.04, 0.028, 0.022]),
+        ([0.03, -0.005, -0.01], -0.12, &[0.055, 0.03, 0.02, 0.018]),
+    ];
+
+    let mut local = Vec::with_capacity(HAND_JOINT_COUNT);
+    local.push((Quat::IDENTITY, Vec3::ZERO)); // palm, placed below
+    local.push((Quat::IDENTITY, Vec3::ZERO)); // wrist
+    for (base, yaw, lengths) in FINGERS {
+        let mut rotation = Quat::from_rotation_y(yaw);
+        let mut position = Vec3::from(base);
+        local.push((rotation, position));
+        for (i, length) in lengths.iter().enumerate() {
+            position += rotation * vec3(0.0, 0.0, -length);
+            // the metacarpal stays straight, every later knuckle bends
+            if i + 1 < lengths.len() {
+                rotation *= Quat::from_rotation_x(-curl * PI / 2.0 * 0.8);
+            }
+            local.push((rotation, position));
+        }
+    }
+    // bend the thumb over by moving each joint past the metacarpal a
+    // share of the way, the tip all of it
+    let reach = local[HandJoint::IndexTip as usize].1 - local[HandJoint::ThumbTip as usize].1;
+    for (i, joint) in [
+        HandJoint::ThumbProximal,
+        HandJoint::ThumbDistal,
+        HandJoint::ThumbTip,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        local[joint as usize].1 += reach * pinch * (i + 1) as f32 / 3.0;
+    }
+    let middle_metacarpal = local[HandJoint::MiddleMetacarpal as usize].1;
+    let middle_proximal = local[HandJoint::MiddleProximal as usize].1;
+    local[HandJoint::Palm as usize].1 = (middle_metacarpal + middle_proximal) / 2.0;
+
+    let joints = local
+        .into_iter()
+        .enumerate()
+        .map(|(i, (rotation, position))| {
+            let (rotation, position) = match hand {
+                Hand::Right => (rotation, position),
+                // mirror across the YZ plane
+                Hand::Left => (
+                    Quat::from_xyzw(rotation.x, -rotation.y, -rotation.z, rotation.w),
+                    vec3(-position.x, position.y, position.z),
+                ),
+            };
+            let tip = matches!(i, 5 | 10 | 15 | 20 | 25);
+            Joint {
+                pose: wrist * Mat4::from_rotation_translation(rotation, position),
+                radius: if tip { 0.008 } else { 0.01 },
+            }
+        })
+        .collect::<Vec<_>>();
+    HandSkeleton {
+        joints: joints.try_into().unwrap(),
+    }
+}
@@ -0,0 +1,36 @@
+// every GL call goes through glow, whose whole API is unsafe
+#![allow(clippy::missing_safety_doc)]
+
+pub mod backend;
+pub mod bounds;
+pub mod camera;
+pub mod ecs;
+pub mod gesture;
+pub mod grab;
+pub mod graph;
+pub mod hand;
+pub mod locomotion;
+pub mod material;
+pub mod mock;
+pub mod model;
+pub mod ray;
+pub mod scene;
+pub mod shader;
+
+pub use backend::{
+    level_pose, Button, ControllerState, FrameView, Hand, HapticPulse, InputState, ReferenceSpace,
+    SharedApp, XrApp, XrBackend,
+};
+pub use bounds::PlayArea;
+pub use camera::{FlyCamera, Movement};
+pub use ecs::{Components, Renderable, Script, System, TrackedDevice, World};
+pub use gesture::{Gesture, GestureConfig, GestureEvent, HandGestures};
+pub use grab::{Collider, GrabSystem, Grabbable};
+pub use graph::{Entity, EntityId, SceneGraph};
+pub use hand::{HandJoint, HandSkeleton, Joint, HAND_JOINT_COUNT};
+pub use locomotion::{Locomotion, LocomotionConfig, Turn};
+pub use material::Material;
+pub use model::{GpuModel, Model, ModelError};
+pub use ray::{Hit, Ray};
+pub use scene::{Pointer, Scene, View, Viewport};
+pub use shader::ShaderVersion;
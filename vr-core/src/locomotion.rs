@@ -0,0 +1,315 @@
+use glam::f32::{vec3, Mat4, Vec3};
+
+use crate::backend::InputState;
+
+/// Thumbstick deflection that starts a teleport aim or a snap turn, and
+/// the one it has to drop back under to finish or re-arm it.
+const STICK_PRESS: f32 = 0.7;
+const STICK_RELEASE: f32 = 0.3;
+
+const GRAVITY: f32 = 9.81;
+/// Seconds between teleport arc points, and the most points one arc has.
+const ARC_STEP: f32 = 0.03;
+const ARC_POINTS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Turn {
+    /// Radians per flick of the stick.
+    Snap(f32),
+    /// Radians per second at full deflection.
+    Smooth(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocomotionConfig {
+    /// Meters per second at full deflection of the left thumbstick.
+    pub move_speed: f32,
+    /// Turning with the right thumbstick's X axis.
+    pub turn: Turn,
+    pub dead_zone: f32,
+    /// Meters per second the teleport arc leaves the right controller at.
+    pub teleport_speed: f32,
+    /// Darken the edges of the view while moving or turning.
+    pub vignette: bool,
+}
+impl Default for LocomotionConfig {
+    fn default() -> LocomotionConfig {
+        LocomotionConfig {
+            move_speed: 2.0,
+            turn: Turn::Snap(std::f32::consts::PI / 6.0),
+            dead_zone: 0.15,
+            teleport_speed: 7.0,
+            vignette: true,
+        }
+    }
+}
+
+/// Moves the stage around a world whose floor is the plane y = 0. The
+/// left thumbstick walks relative to where the head faces, the right one
+/// turns on X and, pushed forward, aims a parabolic teleport arc that is
+/// taken when the stick is let go.
+#[derive(Debug, Clone)]
+pub struct Locomotion {
+    pub config: LocomotionConfig,
+    pub world_from_stage: Mat4,
+    turn_armed: bool,
+    aiming: bool,
+    /// World space points of the teleport arc being aimed, empty otherwise.
+    pub arc: Vec<Vec3>,
+    /// Where the arc lands, if it reaches the floor.
+    pub target: Option<Vec3>,
+    /// 0.0 at rest to 1.0 while moving, for the comfort vignette.
+    pub vignette: f32,
+}
+impl Default for Locomotion {
+    fn default() -> Locomotion {
+        Locomotion::new(LocomotionConfig::default())
+    }
+}
+impl Locomotion {
+    pub fn new(config: LocomotionConfig) -> Locomotion {
+        Locomotion {
+            config,
+            world_from_stage: Mat4::IDENTITY,
+            turn_armed: true,
+            aiming: false,
+            arc: Vec::new(),
+            target: None,
+            vignette: 0.0,
+        }
+    }
+
+    /// `input` is in world space, as the backend delivers it.
+    pub fn update(&mut self, input: &InputState) {
+        let head = match input.head {
+            Some(head) => head,
+            None => return,
+        };
+        let head_position = head.transform_point3(Vec3::ZERO);
+        let dt = input.dt;
+        // smooth motion eases the vignette in, jumps show it at once
+        let mut moved = false;
+        let mut jumped = false;
+
+        let stick = input.left.thumbstick;
+        if stick.length() > self.config.dead_zone {
+            let forward = head.transform_vector3(-Vec3::Z) * vec3(1.0, 0.0, 1.0);
+            let forward = forward.try_normalize().unwrap_or(-Vec3::Z);
+            let right = forward.cross(Vec3::Y);
+            let delta = (right * stick.x + forward * stick.y) * self.config.move_speed * dt;
+            self.world_from_stage = Mat4::from_translation(delta) * self.world_from_stage;
+            moved = true;
+        }
+
+        let turn = input.right.thumbstick.x;
+        let angle = match self.config.turn {
+            Turn::Snap(step) if self.turn_armed && turn.abs() > STICK_PRESS => {
+                self.turn_armed = false;
+                jumped = true;
+                -turn.signum() * step
+            }
+            Turn::Snap(_) => {
+                self.turn_armed |= turn.abs() < STICK_RELEASE;
+                0.0
+            }
+            Turn::Smooth(speed) if turn.abs() > self.config.dead_zone => -turn * speed * dt,
+            Turn::Smooth(_) => 0.0,
+        };
+        if angle != 0.0 {
+            self.world_from_stage = Mat4::from_translation(head_position)
+                * Mat4::from_rotation_y(angle)
+                * Mat4::from_translation(-head_position)
+                * self.world_from_stage;
+            moved |= !jumped;
+        }
+
+        let push = input.right.thumbstick.y;
+        if push > STICK_PRESS {
+            self.aiming = true;
+        }
+        if self.aiming {
+            match input.right.aim {
+                Some(aim) => self.aim(aim),
+                None => self.aiming = false,
+            }
+            if push < STICK_RELEASE {
+                self.aiming = false;
+                if let Some(target) = self.target {
+                    let delta = (target - head_position) * vec3(1.0, 0.0, 1.0);
+                    self.world_from_stage = Mat4::from_translation(delta) * self.world_from_stage;
+                    jumped = true;
+                }
+            }
+        }
+        if !self.aiming {
+            self.arc.clear();
+            self.target = None;
+        }
+
+        if jumped {
+            self.vignette = 1.0;
+        } else {
+            let (goal, rate) = if moved { (1.0, 8.0) } else { (0.0, 3.0) };
+            self.vignette += (goal - self.vignette) * (rate * dt).min(1.0);
+        }
+    }
+
+    /// Samples the arc a ball thrown from the aim pose would fly.
+    fn aim(&mut self, aim: Mat4) {
+        let origin = aim.transform_point3(Vec3::ZERO);
+        let velocity = aim.transform_vector3(-Vec3::Z).normalize() * self.config.teleport_speed;
+        self.arc.clear();
+        self.target = None;
+        if origin.y <= 0.0 {
+            return;
+        }
+        let mut previous = origin;
+        self.arc.push(origin);
+        for i in 1..ARC_POINTS {
+            let t = i as f32 * ARC_STEP;
+            let point = origin + velocity * t - vec3(0.0, GRAVITY / 2.0 * t * t, 0.0);
+            if point.y <= 0.0 {
+                let landing = previous.lerp(point, previous.y / (previous.y - point.y));
+                self.arc.push(landing);
+                self.target = Some(landing);
+                return;
+            }
+            self.arc.push(point);
+            previous = point;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::f32::{vec2, Quat, Vec2};
+
+    use super::*;
+
+    const STEP: f32 = std::f32::consts::PI / 6.0;
+
+    fn input(head: Vec3, left_stick: Vec2, right_stick: Vec2) -> InputState {
+        let mut input = InputState {
+            head: Some(Mat4::from_translation(head)),
+            dt: 0.1,
+            ..InputState::default()
+        };
+        input.left.thumbstick = left_stick;
+        input.right.thumbstick = right_stick;
+        input
+    }
+
+    fn yaw(locomotion: &Locomotion) -> f32 {
+        let (_, rotation, _) = locomotion.world_from_stage.to_scale_rotation_translation();
+        let forward = rotation * -Vec3::Z;
+        (-forward.x).atan2(-forward.z)
+    }
+
+    #[test]
+    fn snap_turn_fires_once_per_push() {
+        let mut locomotion = Locomotion::default();
+        let head = vec3(0.0, 1.6, 0.0);
+        locomotion.update(&input(head, Vec2::ZERO, vec2(1.0, 0.0)));
+        assert_eq!(locomotion.vignette, 1.0);
+        for _ in 0..2 {
+            locomotion.update(&input(head, Vec2::ZERO, vec2(1.0, 0.0)));
+        }
+        assert!((yaw(&locomotion) + STEP).abs() < 1e-5);
+
+        // easing off but not back to center does not re-arm
+        locomotion.update(&input(head, Vec2::ZERO, vec2(0.5, 0.0)));
+        locomotion.update(&input(head, Vec2::ZERO, vec2(1.0, 0.0)));
+        assert!((yaw(&locomotion) + STEP).abs() < 1e-5);
+
+        locomotion.update(&input(head, Vec2::ZERO, Vec2::ZERO));
+        locomotion.update(&input(head, Vec2::ZERO, vec2(-1.0, 0.0)));
+        assert!(yaw(&locomotion).abs() < 1e-5);
+    }
+
+    #[test]
+    fn turns_about_the_head() {
+        let head = vec3(1.0, 1.6, -2.0);
+        for turn in [Turn::Snap(STEP), Turn::Smooth(1.0)] {
+            let mut locomotion = Locomotion::new(LocomotionConfig {
+                turn,
+                ..LocomotionConfig::default()
+            });
+            locomotion.update(&input(head, Vec2::ZERO, vec2(1.0, 0.0)));
+            assert!(yaw(&locomotion).abs() > 0.05);
+            let moved_head = locomotion.world_from_stage.transform_point3(head);
+            assert!(moved_head.abs_diff_eq(head, 1e-5));
+        }
+    }
+
+    #[test]
+    fn smooth_move_follows_the_head_on_the_floor_plane() {
+        let mut locomotion = Locomotion::default();
+        // looking down and to the left still walks level
+        let mut input = input(Vec3::ZERO, vec2(0.0, 1.0), Vec2::ZERO);
+        input.head = Some(Mat4::from_quat(
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2) * Quat::from_rotation_x(-0.5),
+        ));
+        input.dt = 0.5;
+        locomotion.update(&input);
+        let moved = locomotion.world_from_stage.transform_point3(Vec3::ZERO);
+        assert!(moved.abs_diff_eq(vec3(-1.0, 0.0, 0.0), 1e-5));
+        assert!(locomotion.vignette > 0.0);
+
+        // inside the dead zone nothing moves and the vignette fades
+        let vignette = locomotion.vignette;
+        input.left.thumbstick = vec2(0.1, 0.0);
+        locomotion.update(&input);
+        assert!(locomotion
+            .world_from_stage
+            .transform_point3(Vec3::ZERO)
+            .abs_diff_eq(moved, 1e-6));
+        assert!(locomotion.vignette < vignette);
+    }
+
+    #[test]
+    fn teleport_lands_on_the_floor() {
+        let mut locomotion = Locomotion::default();
+        let head = vec3(0.0, 1.6, 0.0);
+        let mut aiming = input(head, Vec2::ZERO, vec2(0.0, 1.0));
+        aiming.right.aim = Some(Mat4::from_rotation_translation(
+            Quat::from_rotation_x(-0.3),
+            vec3(0.2, 1.2, -0.2),
+        ));
+        locomotion.update(&aiming);
+        let target = locomotion.target.unwrap();
+        assert!(target.y.abs() < 1e-6);
+        assert!(target.z < -1.0 && (target.x - 0.2).abs() < 1e-5);
+        assert_eq!(locomotion.arc.last(), Some(&target));
+        assert!(locomotion.arc.iter().all(|point| point.y >= 0.0));
+        // nothing moves until the stick is let go
+        assert_eq!(locomotion.world_from_stage, Mat4::IDENTITY);
+
+        let mut released = aiming.clone();
+        released.right.thumbstick = Vec2::ZERO;
+        locomotion.update(&released);
+        let moved = locomotion.world_from_stage.transform_point3(head);
+        assert!(moved.abs_diff_eq(vec3(target.x, head.y, target.z), 1e-5));
+        assert!(locomotion.arc.is_empty() && locomotion.target.is_none());
+        assert_eq!(locomotion.vignette, 1.0);
+    }
+
+    #[test]
+    fn teleport_arc_that_does_not_come_down_goes_nowhere() {
+        let mut locomotion = Locomotion::new(LocomotionConfig {
+            teleport_speed: 20.0,
+            ..LocomotionConfig::default()
+        });
+        let head = vec3(0.0, 1.6, 0.0);
+        let mut aiming = input(head, Vec2::ZERO, vec2(0.0, 1.0));
+        aiming.right.aim = Some(Mat4::from_rotation_translation(
+            Quat::from_rotation_x(1.5),
+            vec3(0.0, 1.2, 0.0),
+        ));
+        locomotion.update(&aiming);
+        assert_eq!(locomotion.target, None);
+        assert_eq!(locomotion.arc.len(), ARC_POINTS);
+        aiming.right.thumbstick = Vec2::ZERO;
+        locomotion.update(&aiming);
+        assert_eq!(locomotion.world_from_stage, Mat4::IDENTITY);
+    }
+}
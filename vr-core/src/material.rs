@@ -0,0 +1,291 @@
+use glow::HasContext;
+
+/// glTF metallic-roughness material. Texture fields index the textures of
+/// the model it belongs to and are sampled with the first UV set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    /// Linear RGBA, multiplied with the texture.
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness in G and metallic in B, multiplied with the factors.
+    pub metallic_roughness_texture: Option<usize>,
+    /// Tangent space normals, tangents follow the UVs.
+    pub normal_texture: Option<usize>,
+    /// Scales the X and Y of the sampled normals.
+    pub normal_scale: f32,
+    /// Ambient occlusion in R.
+    pub occlusion_texture: Option<usize>,
+    /// From 0.0 (no occlusion) to 1.0 (the full texture).
+    pub occlusion_strength: f32,
+    /// Linear RGB, multiplied with the texture.
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<usize>,
+}
+impl Default for Material {
+    /// The glTF default: white, fully metallic and fully rough.
+    fn default() -> Material {
+        Material {
+            base_color: [1.0; 4],
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive: [0.0; 3],
+            emissive_texture: None,
+        }
+    }
+}
+impl Material {
+    /// Textures holding sRGB encoded colors rather than data.
+    pub fn color_textures(&self) -> impl Iterator<Item = usize> {
+        [self.base_color_texture, self.emissive_texture]
+            .into_iter()
+            .flatten()
+    }
+
+    /// Every texture slot with its sampler uniform, in texture unit order.
+    fn texture_slots(&self) -> [(&'static str, Option<usize>); 5] {
+        [
+            ("base_color_texture", self.base_color_texture),
+            (
+                "metallic_roughness_texture",
+                self.metallic_roughness_texture,
+            ),
+            ("normal_texture", self.normal_texture),
+            ("occlusion_texture", self.occlusion_texture),
+            ("emissive_texture", self.emissive_texture),
+        ]
+    }
+}
+
+pub(crate) const VERTEX_SHADER: &str = r#"
+    uniform mat4 view_projection;
+    uniform mat4 model;
+    uniform vec3 camera_position;
+    layout(location = 0) in vec3 Position;
+    layout(location = 1) in vec3 Normal;
+    layout(location = 2) in vec2 Uv;
+    out vec3 WorldPosition;
+    out vec3 WorldNormal;
+    out vec2 TexCoord;
+    out vec3 CameraPosition;
+    void main() {
+        vec4 world_position = model * vec4(Position, 1);
+        WorldPosition = world_position.xyz;
+        // fine for the uniform scales models are placed with
+        WorldNormal = mat3(model) * Normal;
+        TexCoord = Uv;
+        CameraPosition = camera_position;
+        gl_Position = view_projection * world_position;
+    }"#;
+
+pub(crate) const MULTIVIEW_VERTEX_SHADER: &str = r#"
+    #extension GL_OVR_multiview2 : require
+    layout(num_views = 2) in;
+    uniform mat4 view_projection[2];
+    uniform mat4 model;
+    uniform vec3 camera_position[2];
+    layout(location = 0) in vec3 Position;
+    layout(location = 1) in vec3 Normal;
+    layout(location = 2) in vec2 Uv;
+    out vec3 WorldPosition;
+    out vec3 WorldNormal;
+    out vec2 TexCoord;
+    out vec3 CameraPosition;
+    void main() {
+        vec4 world_position = model * vec4(Position, 1);
+        WorldPosition = world_position.xyz;
+        WorldNormal = mat3(model) * Normal;
+        TexCoord = Uv;
+        CameraPosition = camera_position[gl_ViewID_OVR];
+        gl_Position = view_projection[gl_ViewID_OVR] * world_position;
+    }"#;
+
+/// Cook-Torrance with a GGX distribution, lit by one light from above and
+/// a sky/ground ambient. Neither backend renders into a framebuffer that
+/// encodes on write, so the result is sRGB encoded here.
+pub(crate) const FRAGMENT_SHADER: &str = r#"
+    uniform vec4 base_color;
+    uniform float metallic;
+    uniform float roughness;
+    uniform float normal_scale;
+    uniform float occlusion_strength;
+    uniform vec3 emissive;
+    uniform bool has_base_color_texture;
+    uniform bool has_metallic_roughness_texture;
+    uniform bool has_normal_texture;
+    uniform bool has_occlusion_texture;
+    uniform bool has_emissive_texture;
+    uniform sampler2D base_color_texture;
+    uniform sampler2D metallic_roughness_texture;
+    uniform sampler2D normal_texture;
+    uniform sampler2D occlusion_texture;
+    uniform sampler2D emissive_texture;
+    // mediump positions lose the derivatives far from the origin
+    in highp vec3 WorldPosition;
+    in vec3 WorldNormal;
+    in highp vec2 TexCoord;
+    in highp vec3 CameraPosition;
+    out vec4 FragColor;
+
+    const float PI = 3.14159265;
+    const vec3 LIGHT_DIRECTION = vec3(0.25, 0.83, 0.42);
+    const vec3 LIGHT_COLOR = vec3(2.5);
+    const vec3 SKY_COLOR = vec3(0.35, 0.38, 0.45);
+    const vec3 GROUND_COLOR = vec3(0.15, 0.13, 0.12);
+
+    // without vertex tangents, derive the tangent frame from the screen
+    // space derivatives of the position and UVs
+    vec3 surface_normal(vec3 n) {
+        if (!has_normal_texture) {
+            return n;
+        }
+        // sampled before any per fragment branch, so mipmapping works
+        vec3 m = texture(normal_texture, TexCoord).xyz * 2.0 - 1.0;
+        m.xy *= normal_scale;
+        highp vec3 dp_dx = dFdx(WorldPosition);
+        highp vec3 dp_dy = dFdy(WorldPosition);
+        highp vec2 duv_dx = dFdx(TexCoord);
+        highp vec2 duv_dy = dFdy(TexCoord);
+        highp float det = duv_dx.x * duv_dy.y - duv_dy.x * duv_dx.y;
+        highp vec3 t = (duv_dy.y * dp_dx - duv_dx.y * dp_dy) * sign(det);
+        t -= n * dot(n, t);
+        if (det == 0.0 || dot(t, t) == 0.0) {
+            return n;
+        }
+        t = normalize(t);
+        vec3 b = cross(n, t);
+        return normalize(mat3(t, b, n) * m);
+    }
+
+    void main() {
+        vec4 albedo = base_color;
+        if (has_base_color_texture) {
+            albedo *= texture(base_color_texture, TexCoord);
+        }
+        float metal = metallic;
+        float rough = roughness;
+        if (has_metallic_roughness_texture) {
+            vec4 sampled = texture(metallic_roughness_texture, TexCoord);
+            rough *= sampled.g;
+            metal *= sampled.b;
+        }
+        // very low roughness loses the highlight to mediump precision
+        rough = clamp(rough, 0.05, 1.0);
+        float occlusion = 1.0;
+        if (has_occlusion_texture) {
+            occlusion = mix(1.0, texture(occlusion_texture, TexCoord).r, occlusion_strength);
+        }
+        vec3 emission = emissive;
+        if (has_emissive_texture) {
+            emission *= texture(emissive_texture, TexCoord).rgb;
+        }
+
+        vec3 n = surface_normal(normalize(WorldNormal));
+        vec3 v = normalize(CameraPosition - WorldPosition);
+        vec3 l = normalize(LIGHT_DIRECTION);
+        vec3 h = normalize(l + v);
+        float n_dot_l = max(dot(n, l), 0.0);
+        float n_dot_v = max(dot(n, v), 1e-4);
+        float n_dot_h = max(dot(n, h), 0.0);
+        float v_dot_h = max(dot(v, h), 0.0);
+
+        vec3 f0 = mix(vec3(0.04), albedo.rgb, metal);
+        vec3 diffuse_color = albedo.rgb * (1.0 - metal);
+        vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+        float alpha = rough * rough;
+        float alpha2 = alpha * alpha;
+        float denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+        float distribution = alpha2 / (PI * denominator * denominator);
+        // height-correlated Smith visibility
+        float ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2);
+        float ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2);
+        float visibility = 0.5 / max(ggx_v + ggx_l, 1e-4);
+        vec3 specular = fresnel * distribution * visibility;
+        vec3 diffuse = (1.0 - fresnel) * diffuse_color / PI;
+        vec3 direct = (diffuse + specular) * LIGHT_COLOR * n_dot_l;
+
+        vec3 ambient_light = mix(GROUND_COLOR, SKY_COLOR, n.y * 0.5 + 0.5);
+        vec3 ambient = ambient_light * (diffuse_color + f0 * (1.0 - 0.5 * rough)) * occlusion;
+
+        vec3 color = clamp(direct + ambient + emission, 0.0, 1.0);
+        FragColor = vec4(pow(color, vec3(1.0 / 2.2)), albedo.a);
+    }"#;
+
+/// Uniform locations of a program linked with [`FRAGMENT_SHADER`].
+pub(crate) struct MaterialUniforms<GL: HasContext> {
+    base_color: Option<GL::UniformLocation>,
+    metallic: Option<GL::UniformLocation>,
+    roughness: Option<GL::UniformLocation>,
+    normal_scale: Option<GL::UniformLocation>,
+    occlusion_strength: Option<GL::UniformLocation>,
+    emissive: Option<GL::UniformLocation>,
+    /// The `has_` flag of every texture slot.
+    has_textures: Vec<Option<GL::UniformLocation>>,
+}
+impl<GL: HasContext> MaterialUniforms<GL> {
+    /// Looks up the uniforms and assigns every sampler its texture unit.
+    /// `program` has to be in use.
+    pub unsafe fn new(gl: &GL, program: GL::Program) -> MaterialUniforms<GL> {
+        let slots = Material::default().texture_slots();
+        for (unit, (sampler, _)) in slots.iter().enumerate() {
+            let location = gl.get_uniform_location(program, sampler);
+            gl.uniform_1_i32(location.as_ref(), unit as i32);
+        }
+        MaterialUniforms {
+            base_color: gl.get_uniform_location(program, "base_color"),
+            metallic: gl.get_uniform_location(program, "metallic"),
+            roughness: gl.get_uniform_location(program, "roughness"),
+            normal_scale: gl.get_uniform_location(program, "normal_scale"),
+            occlusion_strength: gl.get_uniform_location(program, "occlusion_strength"),
+            emissive: gl.get_uniform_location(program, "emissive"),
+            has_textures: slots
+                .iter()
+                .map(|(sampler, _)| gl.get_uniform_location(program, &format!("has_{}", sampler)))
+                .collect(),
+        }
+    }
+
+    /// Sets the factors and binds the textures, looked up in `textures`,
+    /// to their units.
+    pub unsafe fn set(&self, gl: &GL, material: &Material, textures: &[GL::Texture]) {
+        let [r, g, b, a] = material.base_color;
+        gl.uniform_4_f32(self.base_color.as_ref(), r, g, b, a);
+        gl.uniform_1_f32(self.metallic.as_ref(), material.metallic);
+        gl.uniform_1_f32(self.roughness.as_ref(), material.roughness);
+        gl.uniform_1_f32(self.normal_scale.as_ref(), material.normal_scale);
+        gl.uniform_1_f32(
+            self.occlusion_strength.as_ref(),
+            material.occlusion_strength,
+        );
+        let [r, g, b] = material.emissive;
+        gl.uniform_3_f32(self.emissive.as_ref(), r, g, b);
+        for (unit, ((_, texture), has_texture)) in material
+            .texture_slots()
+            .into_iter()
+            .zip(&self.has_textures)
+            .enumerate()
+        {
+            let texture = texture.and_then(|i| textures.get(i).copied());
+            gl.uniform_1_i32(has_texture.as_ref(), texture.is_some() as i32);
+            gl.active_texture(glow::TEXTURE0 + unit as u32);
+            gl.bind_texture(glow::TEXTURE_2D, texture);
+        }
+        gl.active_texture(glow::TEXTURE0);
+    }
+
+    /// Unbinds the textures [`MaterialUniforms::set`] bound.
+    pub unsafe fn unbind(&self, gl: &GL) {
+        for unit in 0..self.has_textures.len() {
+            gl.active_texture(glow::TEXTURE0 + unit as u32);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+        }
+        gl.active_texture(glow::TEXTURE0);
+    }
+}
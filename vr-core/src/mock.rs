@@ -0,0 +1,539 @@
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use glam::f32::{vec2, vec3, Mat4, Quat, Vec2};
+use glow::HasContext;
+
+use crate::backend::{
+    ControllerState, FrameView, Hand, HapticPulse, InputState, SharedApp, XrBackend,
+};
+use crate::gesture::HandGestures;
+use crate::hand;
+use crate::scene::{View, Viewport};
+
+/// Session lifecycle of the simulated runtime, mirroring `XrSessionState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    Idle,
+    Ready,
+    Focused,
+    Stopping,
+    Exiting,
+}
+
+/// Tracked poses at one point in time, all in stage space.
+#[derive(Debug, Clone, Default)]
+pub struct SimPose {
+    pub head: Mat4,
+    pub input: InputState,
+}
+
+/// Head bobbing around 1.6m with both controllers circling in front of it,
+/// tracked hands at the same spots: the left one opening and closing, the
+/// right one pinching.
+pub fn orbit_script(time: f64) -> SimPose {
+    let t = time as f32;
+    let head = Mat4::from_rotation_translation(
+        Quat::from_rotation_y((t * 0.5).sin() * PI / 8.0),
+        vec3(0.0, 1.6 + (t * 2.0).sin() * 0.02, 0.0),
+    );
+    let hand = |side: f32| {
+        let angle = t * side;
+        Some(Mat4::from_translation(vec3(
+            side * 0.25 + angle.cos() * 0.1,
+            1.2 + angle.sin() * 0.1,
+            -0.4,
+        )))
+    };
+    let closing = (t.sin() + 1.0) / 2.0;
+    SimPose {
+        head,
+        input: InputState {
+            left: ControllerState {
+                grip: hand(-1.0),
+                aim: hand(-1.0),
+                skeleton: hand(-1.0)
+                    .map(|wrist| hand::synthetic_hand(Hand::Left, wrist, closing, 0.0)),
+                ..Default::default()
+            },
+            right: ControllerState {
+                grip: hand(1.0),
+                aim: hand(1.0),
+                skeleton: hand(1.0)
+                    .map(|wrist| hand::synthetic_hand(Hand::Right, wrist, 0.0, closing)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    }
+}
+
+struct Eye<GL: HasContext> {
+    framebuffer: GL::Framebuffer,
+    color: GL::Texture,
+    depth: GL::Renderbuffer,
+}
+
+/// Backend without a runtime. Runs the session state machine, generates
+/// poses from a script and renders each eye into an offscreen framebuffer,
+/// so the frame loop can run headless (e.g. on an OSMesa context). Started
+/// with [`MockBackend::start_without_gl`] it only updates the app.
+pub struct MockBackend<GL: HasContext> {
+    script: Box<dyn FnMut(f64) -> SimPose>,
+    gestures: HandGestures,
+    state: SessionState,
+    app: Option<SharedApp<GL>>,
+    gl: Option<Rc<GL>>,
+    eyes: Vec<Eye<GL>>,
+    pub eye_width: i32,
+    pub eye_height: i32,
+    pub ipd: f32,
+    pub frame_interval: f64,
+    /// Frames to render before the runtime asks the session to stop.
+    pub frame_limit: Option<usize>,
+    pub frame_count: usize,
+    /// Every haptic pulse the app requested, in order.
+    pub haptics: Vec<HapticPulse>,
+    /// Stage space (x, z) corners handed to the app on start.
+    pub play_area: Vec<Vec2>,
+}
+impl<GL: HasContext> MockBackend<GL> {
+    pub fn new(script: impl FnMut(f64) -> SimPose + 'static) -> MockBackend<GL> {
+        MockBackend {
+            script: Box::new(script),
+            gestures: HandGestures::default(),
+            state: SessionState::Idle,
+            app: None,
+            gl: None,
+            eyes: Vec::new(),
+            eye_width: 256,
+            eye_height: 256,
+            ipd: 0.064,
+            frame_interval: 1.0 / 90.0,
+            frame_limit: None,
+            frame_count: 0,
+            haptics: Vec::new(),
+            play_area: vec![
+                vec2(-1.5, -1.0),
+                vec2(1.5, -1.0),
+                vec2(1.5, 1.0),
+                vec2(-1.5, 1.0),
+            ],
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    pub fn time(&self) -> f64 {
+        self.frame_count as f64 * self.frame_interval
+    }
+
+    /// Color texture the given eye (0 = left) was last rendered into.
+    pub fn eye_texture(&self, eye: usize) -> Option<GL::Texture> {
+        self.eyes.get(eye).map(|eye| eye.color)
+    }
+
+    /// Reads back the RGBA8 pixels of the given eye.
+    pub unsafe fn read_eye(&self, gl: &GL, eye: usize) -> Vec<u8> {
+        let mut pixels = vec![0; (self.eye_width * self.eye_height * 4) as usize];
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.eyes[eye].framebuffer));
+        gl.read_pixels(
+            0,
+            0,
+            self.eye_width,
+            self.eye_height,
+            glow::RGBA,
+            glow::UNSIGNED_BYTE,
+            glow::PixelPackData::Slice(&mut pixels),
+        );
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+        pixels
+    }
+
+    unsafe fn create_eyes(&mut self, gl: &GL) {
+        for _ in 0..2 {
+            let color = gl.create_texture().unwrap();
+            gl.bind_texture(glow::TEXTURE_2D, Some(color));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as _,
+                self.eye_width,
+                self.eye_height,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                None,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::LINEAR as _,
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            let depth = gl.create_renderbuffer().unwrap();
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(depth));
+            gl.renderbuffer_storage(
+                glow::RENDERBUFFER,
+                glow::DEPTH_COMPONENT24,
+                self.eye_width,
+                self.eye_height,
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+            let framebuffer = gl.create_framebuffer().unwrap();
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(color),
+                0,
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_ATTACHMENT,
+                glow::RENDERBUFFER,
+                Some(depth),
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            self.eyes.push(Eye {
+                framebuffer,
+                color,
+                depth,
+            });
+        }
+    }
+
+    unsafe fn delete_eyes(&mut self, gl: &GL) {
+        for eye in self.eyes.drain(..) {
+            gl.delete_framebuffer(eye.framebuffer);
+            gl.delete_texture(eye.color);
+            gl.delete_renderbuffer(eye.depth);
+        }
+    }
+
+    /// Like [`XrBackend::start`], but skips rendering, for driving the
+    /// app's updates where no GL context is available.
+    pub fn start_without_gl(&mut self, app: SharedApp<GL>) {
+        app.borrow_mut().play_area_changed(&self.play_area);
+        self.app = Some(app);
+    }
+
+    fn render_frame(&mut self) {
+        let app = match &self.app {
+            Some(app) => app.clone(),
+            None => return,
+        };
+        let gl = self.gl.clone();
+        if let (Some(gl), true) = (&gl, self.eyes.is_empty()) {
+            unsafe { self.create_eyes(gl) };
+        }
+
+        let time = self.time();
+        let mut pose = (self.script)(time);
+        pose.input.head = Some(pose.head);
+        pose.input.dt = self.frame_interval as f32;
+        self.gestures.apply(&mut pose.input);
+        let mut app = app.borrow_mut();
+        pose.input.transform(app.world_from_stage());
+        app.update(&pose.input);
+        self.haptics.extend(app.take_haptics());
+        let head = app.world_from_stage() * pose.head;
+
+        let aspect = self.eye_width as f32 / self.eye_height as f32;
+        for (i, eye) in self.eyes.iter().enumerate() {
+            let offset = if i == 0 { -self.ipd } else { self.ipd } / 2.0;
+            let eye_pose = head * Mat4::from_translation(vec3(offset, 0.0, 0.0));
+            let view = FrameView {
+                pose: eye_pose,
+                view: View {
+                    viewport: Viewport {
+                        x: 0,
+                        y: 0,
+                        width: self.eye_width,
+                        height: self.eye_height,
+                    },
+                    v_mat: eye_pose.inverse(),
+                    p_mat: Mat4::perspective_rh_gl(PI / 2.0, aspect, 0.1, 100.0),
+                },
+                framebuffer: Some(eye.framebuffer),
+            };
+            if let Some(gl) = &gl {
+                unsafe {
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, view.framebuffer);
+                    app.draw_view(gl, &view);
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                }
+            }
+        }
+        self.frame_count += 1;
+    }
+}
+impl<GL: HasContext> XrBackend<GL> for MockBackend<GL> {
+    fn start(&mut self, gl: Rc<GL>, app: SharedApp<GL>) {
+        self.start_without_gl(app);
+        self.gl = Some(gl);
+    }
+
+    fn stop(&mut self) {
+        if self.state != SessionState::Exiting {
+            self.state = SessionState::Stopping;
+        }
+    }
+
+    /// Advances the session by one state per call until it is focused,
+    /// then renders one frame per call.
+    fn poll(&mut self) -> bool {
+        self.state = match self.state {
+            SessionState::Idle if self.app.is_some() => SessionState::Ready,
+            SessionState::Ready => SessionState::Focused,
+            SessionState::Focused => {
+                self.render_frame();
+                match self.frame_limit {
+                    Some(limit) if self.frame_count >= limit => SessionState::Stopping,
+                    _ => SessionState::Focused,
+                }
+            }
+            SessionState::Stopping => {
+                self.app = None;
+                if let Some(gl) = self.gl.take() {
+                    unsafe { self.delete_eyes(&gl) };
+                }
+                SessionState::Exiting
+            }
+            state => state,
+        };
+        self.state != SessionState::Exiting
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::ffi::c_void;
+
+    use super::*;
+    use crate::backend::XrApp;
+    use crate::scene::Scene;
+    use crate::shader::ShaderVersion;
+
+    /// Counts its updates and asks for one pulse per select press.
+    #[derive(Default)]
+    struct Recorder {
+        updates: usize,
+        play_area: Vec<Vec2>,
+        haptics: Vec<HapticPulse>,
+    }
+    impl XrApp<glow::Context> for Recorder {
+        fn update(&mut self, input: &InputState) {
+            self.updates += 1;
+            if input.right.select.just_pressed() {
+                self.haptics.push(HapticPulse {
+                    hand: Hand::Right,
+                    amplitude: 1.0,
+                    duration: 0.1,
+                    frequency: None,
+                });
+            }
+        }
+
+        unsafe fn draw_view(&mut self, _gl: &glow::Context, _view: &FrameView<glow::Context>) {}
+
+        fn play_area_changed(&mut self, polygon: &[Vec2]) {
+            self.play_area = polygon.to_vec();
+        }
+
+        fn take_haptics(&mut self) -> Vec<HapticPulse> {
+            std::mem::take(&mut self.haptics)
+        }
+    }
+
+    /// Presses select on every second frame.
+    fn clicking_script() -> impl FnMut(f64) -> SimPose {
+        let mut frame = 0;
+        let mut previous = crate::Button::default();
+        move |_| {
+            let select = crate::Button::new(frame % 2 == 1, previous);
+            previous = select;
+            frame += 1;
+            let mut pose = SimPose::default();
+            pose.input.right.select = select;
+            pose
+        }
+    }
+
+    #[test]
+    fn session_runs_to_frame_limit() {
+        let app = Rc::new(RefCell::new(Recorder::default()));
+        let mut backend = MockBackend::<glow::Context>::new(clicking_script());
+        backend.frame_limit = Some(4);
+
+        assert!(backend.poll());
+        assert_eq!(backend.state(), SessionState::Idle, "idle until started");
+
+        backend.start_without_gl(app.clone());
+        assert_eq!(app.borrow().play_area, backend.play_area);
+        let mut states = Vec::new();
+        while backend.poll() {
+            states.push(backend.state());
+        }
+        states.push(backend.state());
+        assert_eq!(
+            states,
+            [
+                SessionState::Ready,
+                SessionState::Focused,
+                SessionState::Focused,
+                SessionState::Focused,
+                SessionState::Focused,
+                SessionState::Stopping,
+                SessionState::Exiting,
+            ]
+        );
+        assert_eq!(backend.frame_count, 4);
+        assert_eq!(app.borrow().updates, 4);
+        assert_eq!(backend.haptics.len(), 2, "one pulse per press");
+        assert!(!backend.poll(), "stays exited");
+        assert_eq!(app.borrow().updates, 4);
+    }
+
+    #[test]
+    fn stop_ends_a_focused_session() {
+        let app = Rc::new(RefCell::new(Recorder::default()));
+        let mut backend = MockBackend::<glow::Context>::new(clicking_script());
+        backend.start_without_gl(app.clone());
+        for _ in 0..5 {
+            assert!(backend.poll());
+        }
+        assert_eq!(backend.frame_count, 3);
+        backend.stop();
+        assert_eq!(backend.state(), SessionState::Stopping);
+        assert!(!backend.poll());
+        assert_eq!(backend.state(), SessionState::Exiting);
+        assert_eq!(app.borrow().updates, 3);
+    }
+
+    /// A current GL 4.1 core context on Mesa's surfaceless EGL platform.
+    /// The library and context are leaked, they live as long as the test.
+    unsafe fn surfaceless_context() -> glow::Context {
+        type GetProcAddress = unsafe extern "C" fn(*const i8) -> *const c_void;
+        let egl = Box::leak(Box::new(
+            libloading::Library::new("libEGL.so.1").expect("libEGL.so.1 unavailable"),
+        ));
+        let get_proc_address = *egl.get::<GetProcAddress>(b"eglGetProcAddress\0").unwrap();
+        let function = |name: &str| {
+            let name = std::ffi::CString::new(name).unwrap();
+            get_proc_address(name.as_ptr())
+        };
+
+        const PLATFORM_SURFACELESS_MESA: u32 = 0x31dd;
+        const OPENGL_API: u32 = 0x30a2;
+        const SURFACE_TYPE: i32 = 0x3033;
+        const PBUFFER_BIT: i32 = 0x0001;
+        const RENDERABLE_TYPE: i32 = 0x3040;
+        const OPENGL_BIT: i32 = 0x0008;
+        const CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+        const CONTEXT_MINOR_VERSION: i32 = 0x30fb;
+        const CONTEXT_OPENGL_PROFILE_MASK: i32 = 0x30fd;
+        const CONTEXT_OPENGL_CORE_PROFILE_BIT: i32 = 0x0001;
+        const NONE: i32 = 0x3038;
+
+        let get_platform_display: unsafe extern "C" fn(
+            u32,
+            *mut c_void,
+            *const isize,
+        ) -> *mut c_void = std::mem::transmute(function("eglGetPlatformDisplay"));
+        let initialize: unsafe extern "C" fn(*mut c_void, *mut i32, *mut i32) -> u32 =
+            std::mem::transmute(function("eglInitialize"));
+        let bind_api: unsafe extern "C" fn(u32) -> u32 =
+            std::mem::transmute(function("eglBindAPI"));
+        let choose_config: unsafe extern "C" fn(
+            *mut c_void,
+            *const i32,
+            *mut *mut c_void,
+            i32,
+            *mut i32,
+        ) -> u32 = std::mem::transmute(function("eglChooseConfig"));
+        let create_context: unsafe extern "C" fn(
+            *mut c_void,
+            *mut c_void,
+            *mut c_void,
+            *const i32,
+        ) -> *mut c_void = std::mem::transmute(function("eglCreateContext"));
+        let make_current: unsafe extern "C" fn(
+            *mut c_void,
+            *mut c_void,
+            *mut c_void,
+            *mut c_void,
+        ) -> u32 = std::mem::transmute(function("eglMakeCurrent"));
+
+        let display = get_platform_display(
+            PLATFORM_SURFACELESS_MESA,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+        );
+        assert!(!display.is_null(), "no surfaceless EGL display");
+        assert_eq!(
+            initialize(display, std::ptr::null_mut(), std::ptr::null_mut()),
+            1
+        );
+        assert_eq!(bind_api(OPENGL_API), 1);
+        let mut config = std::ptr::null_mut();
+        let mut count = 0;
+        // configs default to window surfaces, which surfaceless lacks
+        let attribs = [SURFACE_TYPE, PBUFFER_BIT, RENDERABLE_TYPE, OPENGL_BIT, NONE];
+        assert_eq!(
+            choose_config(display, attribs.as_ptr(), &mut config, 1, &mut count),
+            1
+        );
+        assert_eq!(count, 1, "no OpenGL EGL config");
+        let attribs = [
+            CONTEXT_MAJOR_VERSION,
+            4,
+            CONTEXT_MINOR_VERSION,
+            1,
+            CONTEXT_OPENGL_PROFILE_MASK,
+            CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            NONE,
+        ];
+        let context = create_context(display, config, std::ptr::null_mut(), attribs.as_ptr());
+        assert!(!context.is_null(), "cannot create a GL 4.1 core context");
+        assert_eq!(
+            make_current(display, std::ptr::null_mut(), std::ptr::null_mut(), context),
+            1
+        );
+        glow::Context::from_loader_function(function)
+    }
+
+    #[test]
+    #[ignore = "needs libEGL with Mesa's surfaceless platform"]
+    fn renders_the_scene_headless() {
+        let gl = Rc::new(unsafe { surfaceless_context() });
+        unsafe { gl.enable(glow::DEPTH_TEST) };
+        let scene = Rc::new(RefCell::new(Scene::new(&*gl, ShaderVersion::Gl410)));
+        let mut backend = MockBackend::new(orbit_script);
+        backend.frame_limit = Some(3);
+        backend.start(gl.clone(), scene);
+        while backend.state() != SessionState::Stopping {
+            assert!(backend.poll());
+        }
+        assert_eq!(backend.frame_count, 3);
+        assert_eq!(unsafe { gl.get_error() }, glow::NO_ERROR);
+
+        // the mid triangle is white and sits straight ahead of the head
+        for eye in 0..2 {
+            let pixels = unsafe { backend.read_eye(&gl, eye) };
+            let white = pixels
+                .chunks_exact(4)
+                .filter(|pixel| pixel[..3] == [255, 255, 255])
+                .count();
+            assert!(white > 100, "eye {} has {} white pixels", eye, white);
+        }
+        assert!(!backend.poll());
+    }
+}
@@ -0,0 +1,532 @@
+use std::fmt;
+use std::path::Path;
+
+use glam::f32::{Mat4, Vec3};
+use glow::HasContext;
+
+use crate::material::Material;
+
+/// The sample model, embedded so the wasm build needs no fetch for it.
+pub const SAMPLE_CUBE: &[u8] = include_bytes!("../assets/cube.glb");
+
+#[derive(Debug)]
+pub enum ModelError {
+    Gltf(gltf::Error),
+    MissingPositions { mesh: usize },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Gltf(e) => write!(f, "cannot load glTF: {}", e),
+            ModelError::MissingPositions { mesh } => {
+                write!(f, "a primitive of mesh {} has no positions", mesh)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// Interleaved vertex as uploaded to the vertex buffer.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+impl Vertex {
+    pub const STRIDE: i32 = std::mem::size_of::<Vertex>() as i32;
+    /// Attribute location, component count and byte offset of each field.
+    pub const ATTRIBUTES: [(u32, i32, i32); 3] = [(0, 3, 0), (1, 3, 12), (2, 2, 24)];
+}
+
+/// One draw call's worth of triangles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Primitive {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// The glTF default material when the primitive has none.
+    pub material: Material,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+impl Mesh {
+    /// Minimum and maximum corner of the vertex positions, `None` without
+    /// any vertices.
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        let mut positions = self
+            .primitives
+            .iter()
+            .flat_map(|primitive| &primitive.vertices)
+            .map(|vertex| Vec3::from(vertex.position));
+        let first = positions.next()?;
+        Some(positions.fold((first, first), |(min, max), position| {
+            (min.min(position), max.max(position))
+        }))
+    }
+}
+
+/// RGBA8 pixels, rows from the top. sRGB encoded when a material uses it
+/// for colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Parent-from-node transform.
+    pub transform: Mat4,
+    /// Index into [`Model::meshes`].
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+/// The default scene of a glTF 2.0 asset, decoded on the CPU and ready for
+/// [`GpuModel::new`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<Node>,
+    /// Nodes of the default scene, or every parentless node without one.
+    pub roots: Vec<usize>,
+}
+impl Model {
+    /// Reads a `.gltf` or `.glb` file along with the files it refers to.
+    pub fn load(path: impl AsRef<Path>) -> Result<Model, ModelError> {
+        let (document, buffers, images) = gltf::import(path).map_err(ModelError::Gltf)?;
+        Model::from_gltf(&document, &buffers, &images)
+    }
+
+    /// Parses a `.glb`, or a `.gltf` with only data URIs, from memory.
+    pub fn from_slice(bytes: &[u8]) -> Result<Model, ModelError> {
+        let (document, buffers, images) = gltf::import_slice(bytes).map_err(ModelError::Gltf)?;
+        Model::from_gltf(&document, &buffers, &images)
+    }
+
+    fn from_gltf(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: &[gltf::image::Data],
+    ) -> Result<Model, ModelError> {
+        let meshes = document
+            .meshes()
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives()
+                    .filter(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
+                    .map(|primitive| read_primitive(&primitive, mesh.index(), buffers))
+                    .collect::<Result<_, _>>()?;
+                Ok(Mesh {
+                    name: mesh.name().map(String::from),
+                    primitives,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let textures = images.iter().map(rgba8).collect();
+        let nodes = document
+            .nodes()
+            .map(|node| Node {
+                name: node.name().map(String::from),
+                transform: Mat4::from_cols_array_2d(&node.transform().matrix()),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect::<Vec<_>>();
+        let roots = match document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => (0..nodes.len())
+                .filter(|i| !nodes.iter().any(|node| node.children.contains(i)))
+                .collect(),
+        };
+        Ok(Model {
+            meshes,
+            textures,
+            nodes,
+            roots,
+        })
+    }
+}
+
+fn read_primitive(
+    primitive: &gltf::Primitive,
+    mesh: usize,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Primitive, ModelError> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions = reader
+        .read_positions()
+        .ok_or(ModelError::MissingPositions { mesh })?;
+    // missing UVs sample the first texel
+    let mut normals = reader.read_normals();
+    let mut uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32());
+    let vertices = positions
+        .map(|position| Vertex {
+            position,
+            normal: normals
+                .as_mut()
+                .and_then(Iterator::next)
+                .unwrap_or([0.0, 1.0, 0.0]),
+            uv: uvs.as_mut().and_then(Iterator::next).unwrap_or([0.0, 0.0]),
+        })
+        .collect::<Vec<_>>();
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    let (vertices, indices) = match normals {
+        Some(_) => (vertices, indices),
+        None => flat_shaded(&vertices, &indices),
+    };
+    Ok(Primitive {
+        vertices,
+        indices,
+        material: read_material(&primitive.material()),
+    })
+}
+
+/// Gives every triangle its own vertices with the face normal, as glTF
+/// asks of primitives without normals.
+fn flat_shaded(vertices: &[Vertex], indices: &[u32]) -> (Vec<Vertex>, Vec<u32>) {
+    let vertices = indices
+        .chunks_exact(3)
+        .flat_map(|triangle| {
+            let corners = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+            let [a, b, c] = corners.map(|vertex| Vec3::from(vertex.position));
+            // counter-clockwise triangles face the viewer
+            let normal = (b - a).cross(c - a).try_normalize().unwrap_or(Vec3::Y);
+            corners.map(|vertex| Vertex {
+                normal: normal.into(),
+                ..vertex
+            })
+        })
+        .collect::<Vec<_>>();
+    let indices = (0..vertices.len() as u32).collect();
+    (vertices, indices)
+}
+
+fn read_material(material: &gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+    Material {
+        base_color: pbr.base_color_factor(),
+        base_color_texture: pbr
+            .base_color_texture()
+            .map(|info| info.texture().source().index()),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| info.texture().source().index()),
+        normal_texture: normal
+            .as_ref()
+            .map(|normal| normal.texture().source().index()),
+        normal_scale: normal.as_ref().map_or(1.0, |normal| normal.scale()),
+        occlusion_texture: occlusion
+            .as_ref()
+            .map(|occlusion| occlusion.texture().source().index()),
+        occlusion_strength: occlusion
+            .as_ref()
+            .map_or(1.0, |occlusion| occlusion.strength()),
+        emissive: material.emissive_factor(),
+        emissive_texture: material
+            .emissive_texture()
+            .map(|info| info.texture().source().index()),
+    }
+}
+
+/// Converts a decoded image to RGBA8. Wider channels are scaled down, float
+/// ones clamped to 0.0..=1.0 first.
+fn rgba8(image: &gltf::image::Data) -> Texture {
+    use gltf::image::Format;
+    // channels per texel, bytes per channel
+    let (channels, size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    // decoded images are in native byte order
+    let values = image
+        .pixels
+        .chunks_exact(size)
+        .map(|bytes| match *bytes {
+            [value] => value,
+            [a, b] => ((u16::from_ne_bytes([a, b]) as u32 * 255 + 32767) / 65535) as u8,
+            [a, b, c, d] => {
+                (f32::from_ne_bytes([a, b, c, d]).clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    let pixels = values
+        .chunks_exact(channels)
+        .flat_map(|texel| match *texel {
+            [l] => [l, l, l, 255],
+            [l, a] => [l, l, l, a],
+            [r, g, b] => [r, g, b, 255],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        })
+        .collect();
+    Texture {
+        width: image.width,
+        height: image.height,
+        pixels,
+    }
+}
+
+pub(crate) struct GpuPrimitive<GL: HasContext> {
+    pub vao: GL::VertexArray,
+    pub count: i32,
+    pub material: Material,
+}
+
+/// A [`Model`]'s meshes and textures in GL buffers.
+pub struct GpuModel<GL: HasContext> {
+    /// Indexed like [`Model::meshes`].
+    pub(crate) meshes: Vec<Vec<GpuPrimitive<GL>>>,
+    /// Indexed like [`Model::meshes`], see [`Mesh::bounds`].
+    pub(crate) bounds: Vec<Option<(Vec3, Vec3)>>,
+    buffers: Vec<GL::Buffer>,
+    /// Indexed like [`Model::textures`].
+    pub(crate) textures: Vec<GL::Texture>,
+}
+impl<GL: HasContext> GpuModel<GL> {
+    /// Uploads every mesh and texture. Leaves the last vertex array bound.
+    pub unsafe fn new(gl: &GL, model: &Model) -> GpuModel<GL> {
+        // an image used both for colors and for data is decoded as colors
+        let color_textures = model
+            .meshes
+            .iter()
+            .flat_map(|mesh| &mesh.primitives)
+            .flat_map(|primitive| primitive.material.color_textures())
+            .collect::<Vec<_>>();
+        let textures = model
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(i, texture)| upload_texture(gl, texture, color_textures.contains(&i)))
+            .collect::<Vec<_>>();
+        let mut buffers = Vec::new();
+        let meshes = model
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| {
+                        let vao = gl.create_vertex_array().unwrap();
+                        gl.bind_vertex_array(Some(vao));
+
+                        let vbo = gl.create_buffer().unwrap();
+                        gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+                        gl.buffer_data_u8_slice(
+                            glow::ARRAY_BUFFER,
+                            as_bytes(&primitive.vertices),
+                            glow::STATIC_DRAW,
+                        );
+                        for (location, size, offset) in Vertex::ATTRIBUTES {
+                            gl.enable_vertex_attrib_array(location);
+                            gl.vertex_attrib_pointer_f32(
+                                location,
+                                size,
+                                glow::FLOAT,
+                                false,
+                                Vertex::STRIDE,
+                                offset,
+                            );
+                        }
+
+                        // the element buffer binding is part of the vertex array
+                        let ebo = gl.create_buffer().unwrap();
+                        gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(ebo));
+                        gl.buffer_data_u8_slice(
+                            glow::ELEMENT_ARRAY_BUFFER,
+                            as_bytes(&primitive.indices),
+                            glow::STATIC_DRAW,
+                        );
+                        buffers.extend([vbo, ebo]);
+
+                        GpuPrimitive {
+                            vao,
+                            count: primitive.indices.len() as i32,
+                            material: primitive.material,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        GpuModel {
+            meshes,
+            bounds: model.meshes.iter().map(Mesh::bounds).collect(),
+            buffers,
+            textures,
+        }
+    }
+
+    pub unsafe fn delete(self, gl: &GL) {
+        for primitive in self.meshes.into_iter().flatten() {
+            gl.delete_vertex_array(primitive.vao);
+        }
+        for buffer in self.buffers {
+            gl.delete_buffer(buffer);
+        }
+        for texture in self.textures {
+            gl.delete_texture(texture);
+        }
+    }
+}
+
+unsafe fn upload_texture<GL: HasContext>(gl: &GL, texture: &Texture, srgb: bool) -> GL::Texture {
+    let handle = gl.create_texture().unwrap();
+    gl.bind_texture(glow::TEXTURE_2D, Some(handle));
+    let internal_format = if srgb {
+        glow::SRGB8_ALPHA8
+    } else {
+        glow::RGBA8
+    };
+    gl.tex_image_2d(
+        glow::TEXTURE_2D,
+        0,
+        internal_format as i32,
+        texture.width as i32,
+        texture.height as i32,
+        0,
+        glow::RGBA,
+        glow::UNSIGNED_BYTE,
+        Some(&texture.pixels),
+    );
+    gl.generate_mipmap(glow::TEXTURE_2D);
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_MIN_FILTER,
+        glow::LINEAR_MIPMAP_LINEAR as i32,
+    );
+    gl.tex_parameter_i32(
+        glow::TEXTURE_2D,
+        glow::TEXTURE_MAG_FILTER,
+        glow::LINEAR as i32,
+    );
+    gl.bind_texture(glow::TEXTURE_2D, None);
+    handle
+}
+
+fn as_bytes<T>(slice: &[T]) -> &[u8] {
+    unsafe {
+        core::slice::from_raw_parts(slice.as_ptr() as *const u8, std::mem::size_of_val(slice))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_sample_cube() {
+        let model = Model::from_slice(SAMPLE_CUBE).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let primitives = &model.meshes[0].primitives;
+        assert_eq!(primitives.len(), 1);
+        // flat shaded, four corners and two triangles per face
+        assert_eq!(primitives[0].vertices.len(), 24);
+        assert_eq!(primitives[0].indices.len(), 36);
+        assert!(primitives[0].indices.iter().all(|&index| index < 24));
+        assert_eq!(model.roots, [0]);
+        assert_eq!(model.nodes[0].mesh, Some(0));
+        assert_eq!(
+            model.meshes[0].bounds(),
+            Some((Vec3::splat(-0.5), Vec3::splat(0.5)))
+        );
+    }
+
+    #[test]
+    fn missing_normals_are_flat() {
+        let vertex = |x: f32, y: f32, z: f32| Vertex {
+            position: [x, y, z],
+            normal: [0.0; 3],
+            uv: [0.0; 2],
+        };
+        // a floor and a wall meeting along Z, wound to face up and +X
+        let vertices = [
+            vertex(0.0, 0.0, 0.0),
+            vertex(0.0, 0.0, 1.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+        ];
+        let (vertices, indices) = flat_shaded(&vertices, &[0, 1, 2, 0, 3, 1]);
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+        let normals = vertices
+            .iter()
+            .map(|vertex| vertex.normal)
+            .collect::<Vec<_>>();
+        assert_eq!(normals[..3], [[0.0, 1.0, 0.0]; 3]);
+        assert_eq!(normals[3..], [[1.0, 0.0, 0.0]; 3]);
+        assert_eq!(vertices[4].position, [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn wide_images_become_rgba8() {
+        let image = |format, pixels: Vec<u8>| gltf::image::Data {
+            pixels,
+            format,
+            width: 1,
+            height: 1,
+        };
+        let luma_alpha = [u16::MAX, 0x8000]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        let texture = rgba8(&image(gltf::image::Format::R16G16, luma_alpha));
+        assert_eq!(texture.pixels, [255, 255, 255, 128]);
+        let rgb = [2.0f32, 0.5, -1.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        let texture = rgba8(&image(gltf::image::Format::R32G32B32FLOAT, rgb));
+        assert_eq!(texture.pixels, [255, 128, 0, 255]);
+    }
+
+    #[test]
+    fn vertex_layout_matches_attributes() {
+        assert_eq!(Vertex::STRIDE, 32);
+        let vertex = Vertex {
+            position: [0.0; 3],
+            normal: [0.0; 3],
+            uv: [0.0; 2],
+        };
+        let base = &vertex as *const Vertex as usize;
+        let offset = |field: *const f32| (field as usize - base) as i32;
+        let fields = [
+            (offset(vertex.position.as_ptr()), 3),
+            (offset(vertex.normal.as_ptr()), 3),
+            (offset(vertex.uv.as_ptr()), 2),
+        ];
+        for (location, (attribute, field)) in Vertex::ATTRIBUTES.into_iter().zip(fields).enumerate()
+        {
+            let (attribute_location, size, offset) = attribute;
+            assert_eq!(attribute_location, location as u32);
+            assert_eq!((offset, size), field);
+        }
+    }
+}
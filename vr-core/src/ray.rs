@@ -0,0 +1,172 @@
+use glam::f32::{Mat4, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Unit length.
+    pub direction: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// Meters along the ray.
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Ray along -Z of a world-from-local pose, as aim poses point.
+    pub fn from_pose(pose: Mat4) -> Ray {
+        Ray::new(
+            pose.transform_point3(Vec3::ZERO),
+            pose.transform_vector3(-Vec3::Z),
+        )
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Two-sided Möller–Trumbore intersection.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<Hit> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let to_origin = self.origin - a;
+        let u = to_origin.dot(p) / det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = to_origin.cross(ab);
+        let v = self.direction.dot(q) / det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = ac.dot(q) / det;
+        (distance >= 0.0).then(|| Hit {
+            distance,
+            point: self.at(distance),
+        })
+    }
+
+    /// Slab test against an axis-aligned box. A ray starting inside hits
+    /// where it leaves.
+    pub fn intersect_box(&self, min: Vec3, max: Vec3) -> Option<Hit> {
+        let inverse = self.direction.recip();
+        let to_min = (min - self.origin) * inverse;
+        let to_max = (max - self.origin) * inverse;
+        let near = to_min.min(to_max).max_element();
+        let far = to_min.max(to_max).min_element();
+        if far < near.max(0.0) {
+            return None;
+        }
+        let distance = if near >= 0.0 { near } else { far };
+        Some(Hit {
+            distance,
+            point: self.at(distance),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Right triangle in the z = 0 plane, corner at the origin.
+    const TRIANGLE: [Vec3; 3] = [Vec3::ZERO, Vec3::X, Vec3::Y];
+
+    fn intersect(ray: Ray) -> Option<Hit> {
+        let [a, b, c] = TRIANGLE;
+        ray.intersect_triangle(a, b, c)
+    }
+
+    #[test]
+    fn hits_triangle() {
+        let hit = intersect(Ray::new(Vec3::new(0.25, 0.25, 2.0), -Vec3::Z)).unwrap();
+        assert!((hit.distance - 2.0).abs() < 1e-5);
+        assert!(hit.point.abs_diff_eq(Vec3::new(0.25, 0.25, 0.0), 1e-5));
+        // at a slant, distance is along the ray
+        let hit = intersect(Ray::new(
+            Vec3::new(0.25, -0.75, 1.0),
+            Vec3::new(0.0, 1.0, -1.0),
+        ))
+        .unwrap();
+        assert!((hit.distance - 2f32.sqrt()).abs() < 1e-5);
+        assert!(hit.point.abs_diff_eq(Vec3::new(0.25, 0.25, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn misses_beside_triangle() {
+        // inside the bounding square, past the hypotenuse
+        assert_eq!(
+            intersect(Ray::new(Vec3::new(0.6, 0.6, 1.0), -Vec3::Z)),
+            None
+        );
+        assert_eq!(
+            intersect(Ray::new(Vec3::new(-0.1, 0.5, 1.0), -Vec3::Z)),
+            None
+        );
+        assert_eq!(
+            intersect(Ray::new(Vec3::new(0.5, -0.1, 1.0), -Vec3::Z)),
+            None
+        );
+    }
+
+    #[test]
+    fn hits_back_face_but_not_edge_on() {
+        let hit = intersect(Ray::new(Vec3::new(0.25, 0.25, -1.0), Vec3::Z)).unwrap();
+        assert!((hit.distance - 1.0).abs() < 1e-5);
+        // parallel to the triangle's plane, even within it
+        assert_eq!(
+            intersect(Ray::new(Vec3::new(-1.0, 0.25, 0.0), Vec3::X)),
+            None
+        );
+        assert_eq!(
+            intersect(Ray::new(Vec3::new(-1.0, 0.25, 1.0), Vec3::X)),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_triangle_behind_origin() {
+        assert_eq!(
+            intersect(Ray::new(Vec3::new(0.25, 0.25, 1.0), Vec3::Z)),
+            None
+        );
+    }
+
+    #[test]
+    fn hits_box_front_face() {
+        let ray = Ray::new(Vec3::new(0.2, 0.0, 5.0), -Vec3::Z);
+        let hit = ray.intersect_box(Vec3::splat(-1.0), Vec3::ONE).unwrap();
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+        assert!(hit.point.abs_diff_eq(Vec3::new(0.2, 0.0, 1.0), 1e-5));
+    }
+
+    #[test]
+    fn hits_box_from_inside_where_it_leaves() {
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        let hit = ray.intersect_box(Vec3::splat(-1.0), Vec3::ONE).unwrap();
+        assert!((hit.distance - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn misses_box_beside_or_behind() {
+        let min = Vec3::splat(-1.0);
+        let beside = Ray::new(Vec3::new(2.0, 0.0, 5.0), -Vec3::Z);
+        assert_eq!(beside.intersect_box(min, Vec3::ONE), None);
+        let behind = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::Z);
+        assert_eq!(behind.intersect_box(min, Vec3::ONE), None);
+    }
+}
@@ -0,0 +1,711 @@
+use glam::f32::{vec3, Mat4, Vec2, Vec3, Vec4};
+use glow::HasContext;
+
+use crate::backend::{self, FrameView, Hand, HapticPulse, InputState, XrApp};
+use crate::bounds::PlayArea;
+use crate::ecs::{Renderable, TrackedDevice, World};
+use crate::grab::{Collider, GrabSystem, Grabbable};
+use crate::graph::EntityId;
+use crate::hand::HandSkeleton;
+use crate::locomotion::Locomotion;
+use crate::material::{self, Material, MaterialUniforms};
+use crate::model::{self, GpuModel, Model};
+use crate::ray::{Hit, Ray};
+use crate::shader::{self, ShaderVersion};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// One eye (or the desktop window) to render the scene from.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    pub viewport: Viewport,
+    pub v_mat: Mat4,
+    pub p_mat: Mat4,
+}
+
+/// Model space vertices of every triangle in the scene.
+const TRIANGLE: [[f32; 3]; 3] = [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [-1.0, 0.0, 0.0]];
+
+/// Pointer rays that hit nothing are drawn this long, in meters.
+const RAY_LENGTH: f32 = 5.0;
+
+/// What a vertex buffer range holds: the triangle, then a unit line down -Z.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Primitive {
+    Triangle,
+    Line,
+}
+impl Primitive {
+    /// Draw mode, first vertex and vertex count.
+    fn range(self) -> (u32, i32, i32) {
+        match self {
+            Primitive::Triangle => (glow::TRIANGLES, 0, 3),
+            Primitive::Line => (glow::LINES, 3, 2),
+        }
+    }
+}
+
+/// A controller's laser pointer.
+#[derive(Debug, Clone, Copy)]
+pub struct Pointer {
+    /// World-from-aim transform, the ray points down -Z.
+    pub aim: Mat4,
+    pub hit: Option<Hit>,
+}
+
+const VERTEX_SHADER: &str = r#"
+    uniform mat4 mvp;
+    in vec3 Position;
+    void main() {
+        gl_Position = mvp * vec4(Position, 1);
+    }"#;
+
+const MULTIVIEW_VERTEX_SHADER: &str = r#"
+    #extension GL_OVR_multiview2 : require
+    layout(num_views = 2) in;
+    uniform mat4 view_projection[2];
+    uniform mat4 model;
+    in vec3 Position;
+    void main() {
+        gl_Position = view_projection[gl_ViewID_OVR] * model * vec4(Position, 1);
+    }"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    uniform vec3 color;
+    out vec4 FragColor;
+    void main() {
+        FragColor = vec4(color, 1);
+    }"#;
+
+/// Full-screen triangle darkening the edges of the view while moving.
+const VIGNETTE_VERTEX_SHADER: &str = r#"
+    out vec2 Ndc;
+    void main() {
+        Ndc = vec2(float((gl_VertexID & 1) << 2) - 1.0, float((gl_VertexID & 2) << 1) - 1.0);
+        gl_Position = vec4(Ndc, 0, 1);
+    }"#;
+
+const VIGNETTE_FRAGMENT_SHADER: &str = r#"
+    uniform float strength;
+    in vec2 Ndc;
+    out vec4 FragColor;
+    void main() {
+        float edge = smoothstep(1.0 - 0.6 * strength, 1.3 - 0.6 * strength, length(Ndc));
+        FragColor = vec4(0, 0, 0, edge * strength);
+    }"#;
+
+pub struct Scene<GL: HasContext> {
+    program: GL::Program,
+    model_program: GL::Program,
+    /// Present when the context supports `GL_OVR_multiview2`.
+    model_multiview_program: Option<GL::Program>,
+    vignette_program: GL::Program,
+    /// Present when the context supports `GL_OVR_multiview2`.
+    multiview_program: Option<GL::Program>,
+    vbo: GL::Buffer,
+    vao: GL::VertexArray,
+    pub p_mat: Mat4,
+    pub v_mat: Mat4,
+    /// Entities with a [`Collider`] can be grabbed, the first one is the
+    /// mid triangle.
+    pub world: World,
+    grab: GrabSystem,
+    /// Indexed by [`Hand`], tracking the grip pose.
+    pub controllers: [EntityId; 2],
+    /// Indexed by [`Hand`], `None` while the aim pose is not tracked.
+    pub pointers: [Option<Pointer>; 2],
+    /// Indexed by [`Hand`], drawn as a debug skeleton while tracked.
+    pub skeletons: [Option<HandSkeleton>; 2],
+    pub locomotion: Locomotion,
+    /// Drawn as a floor outline, with walls near the head and controllers.
+    pub play_area: PlayArea,
+    pub models: Vec<GpuModel<GL>>,
+    haptics: Vec<HapticPulse>,
+}
+impl<GL: HasContext> Scene<GL> {
+    pub fn new(gl: &GL, version: ShaderVersion) -> Scene<GL> {
+        let program = unsafe {
+            shader::create_program(
+                gl,
+                version,
+                &[
+                    (glow::VERTEX_SHADER, VERTEX_SHADER),
+                    (glow::FRAGMENT_SHADER, FRAGMENT_SHADER),
+                ],
+            )
+        };
+
+        let vignette_program = unsafe {
+            shader::create_program(
+                gl,
+                version,
+                &[
+                    (glow::VERTEX_SHADER, VIGNETTE_VERTEX_SHADER),
+                    (glow::FRAGMENT_SHADER, VIGNETTE_FRAGMENT_SHADER),
+                ],
+            )
+        };
+
+        let multiview_program = if gl.supported_extensions().contains("GL_OVR_multiview2") {
+            Some(unsafe {
+                shader::create_program(
+                    gl,
+                    version,
+                    &[
+                        (glow::VERTEX_SHADER, MULTIVIEW_VERTEX_SHADER),
+                        (glow::FRAGMENT_SHADER, FRAGMENT_SHADER),
+                    ],
+                )
+            })
+        } else {
+            None
+        };
+
+        let model_program = unsafe {
+            shader::create_program(
+                gl,
+                version,
+                &[
+                    (glow::VERTEX_SHADER, material::VERTEX_SHADER),
+                    (glow::FRAGMENT_SHADER, material::FRAGMENT_SHADER),
+                ],
+            )
+        };
+        let model_multiview_program = multiview_program.map(|_| unsafe {
+            shader::create_program(
+                gl,
+                version,
+                &[
+                    (glow::VERTEX_SHADER, material::MULTIVIEW_VERTEX_SHADER),
+                    (glow::FRAGMENT_SHADER, material::FRAGMENT_SHADER),
+                ],
+            )
+        });
+
+        let (vbo, vao) = unsafe {
+            let mut vertices = TRIANGLE.concat();
+            vertices.extend([0.0, 0.0, 0.0, 0.0, 0.0, -1.0]);
+            let vertices_u8: &[u8] = core::slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                vertices.len() * core::mem::size_of::<f32>(),
+            );
+
+            let vbo = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, vertices_u8, glow::STATIC_DRAW);
+
+            let vao = gl.create_vertex_array().unwrap();
+            gl.bind_vertex_array(Some(vao));
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, 12, 0);
+
+            (vbo, vao)
+        };
+
+        let mut world = World::default();
+        let mid = world.spawn(
+            "mid triangle",
+            Mat4::from_translation(vec3(0.0, 0.0, -3.0)),
+            None,
+        );
+        let small = world.spawn(
+            "small triangle",
+            Mat4::from_scale_rotation_translation(
+                Vec3::splat(0.1),
+                glam::Quat::IDENTITY,
+                vec3(0.0, 1.1, -0.5),
+            ),
+            None,
+        );
+        for id in [mid, small] {
+            world.renderables.insert(id, Renderable::Triangle);
+            world.colliders.insert(id, triangle_collider());
+        }
+        let controllers = [
+            (Hand::Left, "left controller", [1.0, 0.0, 0.0, 1.0]),
+            (Hand::Right, "right controller", [0.0, 1.0, 0.0, 1.0]),
+        ]
+        .map(|(hand, name, base_color)| {
+            let controller = world.spawn(name, Mat4::IDENTITY, None);
+            world.graph[controller].visible = false;
+            world.tracked.insert(controller, TrackedDevice::Grip(hand));
+            let model = world.spawn(
+                &format!("{} model", name),
+                controller_model_matrix(Mat4::IDENTITY),
+                Some(controller),
+            );
+            world.renderables.insert(model, Renderable::Triangle);
+            world.materials.insert(
+                model,
+                Material {
+                    base_color,
+                    ..Material::default()
+                },
+            );
+            controller
+        });
+
+        let mut scene = Scene {
+            program,
+            model_program,
+            model_multiview_program,
+            vignette_program,
+            multiview_program,
+            vbo,
+            vao,
+            p_mat: Mat4::perspective_rh_gl(std::f32::consts::PI / 2.0, 1.0, 0.1, 100.0),
+            v_mat: Mat4::look_at_rh(
+                vec3(0.0, 1.0, 3.0),
+                vec3(0.0, 0.0, 0.0),
+                vec3(0.0, 1.0, 0.0),
+            ),
+            world,
+            grab: GrabSystem::default(),
+            controllers,
+            pointers: [None, None],
+            skeletons: [None, None],
+            locomotion: Locomotion::default(),
+            play_area: PlayArea::default(),
+            models: Vec::new(),
+            haptics: Vec::new(),
+        };
+        let cube = Model::from_slice(model::SAMPLE_CUBE).expect("Sample model is malformed");
+        unsafe {
+            scene.add_model(
+                gl,
+                &cube,
+                Mat4::from_scale_rotation_translation(
+                    Vec3::splat(0.15),
+                    glam::Quat::IDENTITY,
+                    vec3(0.6, 1.0, -1.0),
+                ),
+            )
+        };
+        scene
+    }
+
+    /// Uploads `model` and adds its node hierarchy under a new root entity
+    /// placed at `world_from_model`. Returns that root.
+    pub unsafe fn add_model(&mut self, gl: &GL, model: &Model, world_from_model: Mat4) -> EntityId {
+        let index = self.models.len();
+        self.models.push(GpuModel::new(gl, model));
+        gl.bind_vertex_array(Some(self.vao));
+
+        let root = self.world.spawn("", world_from_model, None);
+        let mut stack = model
+            .roots
+            .iter()
+            .map(|&node| (node, root))
+            .collect::<Vec<_>>();
+        while let Some((node, parent)) = stack.pop() {
+            let node = &model.nodes[node];
+            let id = self.world.spawn(
+                node.name.as_deref().unwrap_or(""),
+                node.transform,
+                Some(parent),
+            );
+            if let Some(mesh) = node.mesh {
+                self.world
+                    .renderables
+                    .insert(id, Renderable::Mesh { model: index, mesh });
+            }
+            stack.extend(node.children.iter().map(|&child| (child, id)));
+        }
+        root
+    }
+
+    pub unsafe fn clear(&self, gl: &GL) {
+        gl.clear_color(0.0, 0.0, 0.0, 1.0);
+        gl.clear(glow::COLOR_BUFFER_BIT | glow::DEPTH_BUFFER_BIT);
+    }
+
+    /// Renders into the currently bound framebuffer, keeping the view's matrices.
+    pub unsafe fn render_view(&mut self, gl: &GL, view: &View) {
+        let viewport = view.viewport;
+        gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+        self.v_mat = view.v_mat;
+        self.p_mat = view.p_mat;
+        self.render(gl);
+    }
+
+    pub unsafe fn render(&self, gl: &GL) {
+        gl.use_program(Some(self.program));
+        gl.bind_vertex_array(Some(self.vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+
+        let mvp_location = gl.get_uniform_location(self.program, "mvp");
+        let color_location = gl.get_uniform_location(self.program, "color");
+        for (primitive, m_mat, color) in self.draws() {
+            let mvp_mat = self.p_mat * self.v_mat * m_mat;
+            gl.uniform_matrix_4_f32_slice(mvp_location.as_ref(), false, &mvp_mat.to_cols_array());
+            gl.uniform_3_f32(color_location.as_ref(), color[0], color[1], color[2]);
+            let (mode, first, count) = primitive.range();
+            gl.draw_arrays(mode, first, count);
+        }
+
+        gl.use_program(Some(self.model_program));
+        let view_projection = self.p_mat * self.v_mat;
+        let location = gl.get_uniform_location(self.model_program, "view_projection");
+        gl.uniform_matrix_4_f32_slice(location.as_ref(), false, &view_projection.to_cols_array());
+        let camera_position = self.v_mat.inverse().w_axis;
+        let location = gl.get_uniform_location(self.model_program, "camera_position");
+        gl.uniform_3_f32(
+            location.as_ref(),
+            camera_position.x,
+            camera_position.y,
+            camera_position.z,
+        );
+        self.render_entities(gl, self.model_program);
+    }
+
+    /// Draws every entity with a mesh, in its material if it has one, with
+    /// `program` bound and its view projection and camera position set.
+    unsafe fn render_entities(&self, gl: &GL, program: GL::Program) {
+        let model_location = gl.get_uniform_location(program, "model");
+        let materials = MaterialUniforms::new(gl, program);
+        for (id, &renderable) in self.world.renderables.iter() {
+            let entity = &self.world.graph[id];
+            let (model, mesh) = match renderable {
+                Renderable::Mesh { model, mesh } if entity.is_world_visible() => (model, mesh),
+                _ => continue,
+            };
+            let m_mat = entity.world().to_cols_array();
+            gl.uniform_matrix_4_f32_slice(model_location.as_ref(), false, &m_mat);
+            let model = &self.models[model];
+            let material = self.world.materials.get(id);
+            for primitive in &model.meshes[mesh] {
+                let material = material.unwrap_or(&primitive.material);
+                materials.set(gl, material, &model.textures);
+                gl.bind_vertex_array(Some(primitive.vao));
+                gl.draw_elements(glow::TRIANGLES, primitive.count, glow::UNSIGNED_INT, 0);
+            }
+        }
+        materials.unbind(gl);
+        gl.bind_vertex_array(Some(self.vao));
+    }
+
+    /// Blends the comfort vignette over the current viewport.
+    pub unsafe fn render_vignette(&self, gl: &GL) {
+        let strength = self.locomotion.vignette;
+        if !self.locomotion.config.vignette || strength < 0.01 {
+            return;
+        }
+        gl.use_program(Some(self.vignette_program));
+        let strength_location = gl.get_uniform_location(self.vignette_program, "strength");
+        gl.uniform_1_f32(strength_location.as_ref(), strength);
+        gl.disable(glow::DEPTH_TEST);
+        gl.enable(glow::BLEND);
+        gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+        gl.draw_arrays(glow::TRIANGLES, 0, 3);
+        gl.disable(glow::BLEND);
+        gl.enable(glow::DEPTH_TEST);
+    }
+
+    /// Renders both views in one pass into a two-layer multiview framebuffer.
+    /// Returns `false` without drawing when multiview is unsupported.
+    pub unsafe fn render_multiview(&self, gl: &GL, views: &[View]) -> bool {
+        let program = match self.multiview_program {
+            Some(program) if views.len() == 2 => program,
+            _ => return false,
+        };
+        gl.use_program(Some(program));
+        gl.bind_vertex_array(Some(self.vao));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vbo));
+
+        let view_projection = views
+            .iter()
+            .flat_map(|view| (view.p_mat * view.v_mat).to_cols_array())
+            .collect::<Vec<_>>();
+        let uniform_location = gl.get_uniform_location(program, "view_projection");
+        gl.uniform_matrix_4_f32_slice(uniform_location.as_ref(), false, &view_projection);
+
+        let model_location = gl.get_uniform_location(program, "model");
+        let color_location = gl.get_uniform_location(program, "color");
+        for (primitive, m_mat, color) in self.draws() {
+            gl.uniform_matrix_4_f32_slice(model_location.as_ref(), false, &m_mat.to_cols_array());
+            gl.uniform_3_f32(color_location.as_ref(), color[0], color[1], color[2]);
+            let (mode, first, count) = primitive.range();
+            gl.draw_arrays(mode, first, count);
+        }
+
+        if let Some(model_program) = self.model_multiview_program {
+            gl.use_program(Some(model_program));
+            let location = gl.get_uniform_location(model_program, "view_projection");
+            gl.uniform_matrix_4_f32_slice(location.as_ref(), false, &view_projection);
+            let camera_positions = views
+                .iter()
+                .flat_map(|view| view.v_mat.inverse().w_axis.truncate().to_array())
+                .collect::<Vec<_>>();
+            let location = gl.get_uniform_location(model_program, "camera_position");
+            gl.uniform_3_f32_slice(location.as_ref(), &camera_positions);
+            self.render_entities(gl, model_program);
+        }
+        true
+    }
+
+    /// Everything to draw with its model matrix and color: the triangle
+    /// entities, objects blue while held, then the pointer rays, the bones
+    /// of tracked hands, the teleport arc and the play area. Rays
+    /// end where they hit and turn yellow; the arc is green when it reaches
+    /// the floor. Boundary walls fade in from black.
+    fn draws(&self) -> impl Iterator<Item = (Primitive, Mat4, [f32; 3])> + '_ {
+        let held = [Hand::Left, Hand::Right].map(|hand| self.grab.held(hand));
+        let triangles = self
+            .world
+            .renderables
+            .iter()
+            .filter(|&(id, &renderable)| {
+                renderable == Renderable::Triangle && self.world.graph[id].is_world_visible()
+            })
+            .map(move |(id, _)| {
+                let color = if held.contains(&Some(id)) {
+                    [0.0, 0.5, 1.0]
+                } else {
+                    let [r, g, b, _] = self.base_color(id);
+                    [r, g, b]
+                };
+                (self.world.graph[id].world(), color)
+            });
+        let rays = self.pointers.map(|pointer| {
+            pointer.map(|pointer| {
+                let (length, color) = match pointer.hit {
+                    Some(hit) => (hit.distance, [1.0, 1.0, 0.0]),
+                    None => (RAY_LENGTH, [1.0, 1.0, 1.0]),
+                };
+                (
+                    pointer.aim * Mat4::from_scale(vec3(1.0, 1.0, length)),
+                    color,
+                )
+            })
+        });
+        let bones = self
+            .skeletons
+            .iter()
+            .flatten()
+            .flat_map(|skeleton| skeleton.bones())
+            .map(|(start, end)| (segment_model_matrix(start, end), [0.0, 1.0, 1.0]));
+        let arc_color = if self.locomotion.target.is_some() {
+            [0.0, 1.0, 0.0]
+        } else {
+            [1.0, 0.0, 0.0]
+        };
+        let arc = self
+            .locomotion
+            .arc
+            .windows(2)
+            .map(move |points| (segment_model_matrix(points[0], points[1]), arc_color));
+        let world_from_stage = self.locomotion.world_from_stage;
+        let segment = move |start, end| {
+            segment_model_matrix(
+                world_from_stage.transform_point3(start),
+                world_from_stage.transform_point3(end),
+            )
+        };
+        let outline = self
+            .play_area
+            .outline()
+            .map(move |(start, end)| (segment(start, end), [0.3, 0.3, 0.3]));
+        let walls = self
+            .play_area
+            .walls()
+            .map(move |(start, end, fade)| (segment(start, end), [0.0, fade * 0.6, fade]));
+        let lines = rays
+            .into_iter()
+            .flatten()
+            .chain(bones)
+            .chain(arc)
+            .chain(outline)
+            .chain(walls);
+        triangles
+            .map(|(m_mat, color)| (Primitive::Triangle, m_mat, color))
+            .chain(lines.map(|(m_mat, color)| (Primitive::Line, m_mat, color)))
+    }
+
+    /// Linear RGBA of the entity's material, white without one.
+    fn base_color(&self, id: EntityId) -> [f32; 4] {
+        self.world
+            .materials
+            .get(id)
+            .map_or([1.0; 4], |material| material.base_color)
+    }
+
+    /// Closest hit of the ray against the grabbable triangles and the
+    /// bounding boxes of the visible meshes.
+    pub fn raycast(&self, ray: &Ray) -> Option<Hit> {
+        let triangles = self
+            .world
+            .colliders
+            .iter()
+            .filter(|&(id, _)| self.world.renderables.get(id) == Some(&Renderable::Triangle))
+            .filter_map(|(id, _)| {
+                let world = self.world.graph[id].world();
+                let [a, b, c] = TRIANGLE.map(|vertex| world.transform_point3(Vec3::from(vertex)));
+                ray.intersect_triangle(a, b, c)
+            });
+        let meshes = self
+            .world
+            .renderables
+            .iter()
+            .filter_map(|(id, &renderable)| match renderable {
+                Renderable::Mesh { model, mesh } if self.world.graph[id].is_world_visible() => {
+                    Some((id, self.models[model].bounds[mesh]?))
+                }
+                _ => None,
+            })
+            .filter_map(|(id, (min, max))| {
+                // boxes are in mesh space, so hit them there
+                let world = self.world.graph[id].world();
+                let local = world.inverse();
+                let local_ray = Ray::new(
+                    local.transform_point3(ray.origin),
+                    local.transform_vector3(ray.direction),
+                );
+                let point = world.transform_point3(local_ray.intersect_box(min, max)?.point);
+                Some(Hit {
+                    distance: point.distance(ray.origin),
+                    point,
+                })
+            });
+        triangles
+            .chain(meshes)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+impl<GL: HasContext> XrApp<GL> for Scene<GL> {
+    /// Runs the world's systems, then grabbing. Controllers that lose
+    /// tracking keep their last known pose, pointers disappear. Pressing
+    /// select or grabbing an object buzzes that controller.
+    fn update(&mut self, input: &InputState) {
+        // the backend placed the poses with last frame's stage, move them
+        // along with this frame's locomotion
+        let world_from_stage = self.locomotion.world_from_stage;
+        self.locomotion.update(input);
+        let mut input = input.clone();
+        input.transform(self.locomotion.world_from_stage * world_from_stage.inverse());
+        let input = &input;
+
+        let stage_from_world = self.locomotion.world_from_stage.inverse();
+        let tracked = [input.head, input.left.grip, input.right.grip]
+            .into_iter()
+            .flatten()
+            .map(|pose| stage_from_world.transform_point3(pose.transform_point3(Vec3::ZERO)))
+            .collect::<Vec<_>>();
+        self.play_area.update(&tracked);
+
+        self.world.update(input);
+
+        let mut objects = self
+            .world
+            .colliders
+            .iter()
+            .map(|(id, &collider)| Grabbable {
+                id,
+                transform: self.world.graph[id].world(),
+                collider,
+            })
+            .collect::<Vec<_>>();
+        let grabbed = self.grab.update(input, &mut objects);
+        for object in &objects {
+            self.world.graph.set_world(object.id, object.transform);
+        }
+        self.world.graph.propagate();
+        for hand in grabbed {
+            self.haptics.push(HapticPulse {
+                hand,
+                amplitude: 0.3,
+                duration: 0.02,
+                frequency: None,
+            });
+        }
+        for hand in [Hand::Left, Hand::Right] {
+            self.skeletons[hand as usize] = input.hand(hand).skeleton;
+            self.pointers[hand as usize] = input.hand(hand).aim.map(|aim| Pointer {
+                aim,
+                hit: self.raycast(&Ray::from_pose(aim)),
+            });
+            if input.hand(hand).select.just_pressed() {
+                self.haptics.push(HapticPulse {
+                    hand,
+                    amplitude: 0.5,
+                    duration: 0.05,
+                    frequency: None,
+                });
+            }
+        }
+    }
+
+    unsafe fn draw_view(&mut self, gl: &GL, view: &FrameView<GL>) {
+        backend::set_viewport(gl, view);
+        self.clear(gl);
+        self.render_view(gl, &view.view);
+        self.render_vignette(gl);
+        gl.disable(glow::SCISSOR_TEST);
+    }
+
+    unsafe fn draw_multiview(&mut self, gl: &GL, views: &[FrameView<GL>]) -> bool {
+        if self.multiview_program.is_none() {
+            return false;
+        }
+        backend::set_viewport(gl, &views[0]);
+        self.clear(gl);
+        let views = views.iter().map(|view| view.view).collect::<Vec<_>>();
+        // the vignette program has no multiview variant, so a frame with
+        // the vignette showing falls back to drawing each view
+        if self.locomotion.config.vignette && self.locomotion.vignette >= 0.01 {
+            return false;
+        }
+        let drawn = self.render_multiview(gl, &views);
+        gl.disable(glow::SCISSOR_TEST);
+        drawn
+    }
+
+    fn world_from_stage(&self) -> Mat4 {
+        self.locomotion.world_from_stage
+    }
+
+    /// Keeps the world where it was in the room, so it does not jump.
+    fn reference_space_changed(&mut self, previous_from_new: Option<Mat4>) {
+        if let Some(previous_from_new) = previous_from_new {
+            self.locomotion.world_from_stage *= previous_from_new;
+        }
+    }
+
+    fn play_area_changed(&mut self, polygon: &[Vec2]) {
+        self.play_area.set_polygon(polygon);
+    }
+
+    fn take_haptics(&mut self) -> Vec<HapticPulse> {
+        std::mem::take(&mut self.haptics)
+    }
+}
+
+/// Maps the unit line primitive onto the segment from `start` to `end`.
+fn segment_model_matrix(start: Vec3, end: Vec3) -> Mat4 {
+    Mat4::from_cols(
+        Vec4::X,
+        Vec4::Y,
+        (start - end).extend(0.0),
+        start.extend(1.0),
+    )
+}
+
+/// A slab around the triangle, so it can be grabbed from slightly in front
+/// or behind.
+fn triangle_collider() -> Collider {
+    Collider::Box {
+        center: vec3(0.0, 0.5, 0.0),
+        half_extents: vec3(1.0, 0.5, 0.5),
+    }
+}
+
+pub fn controller_model_matrix(grip: Mat4) -> Mat4 {
+    grip * Mat4::from_scale(vec3(0.1, 0.1, 0.1))
+        * Mat4::from_rotation_x(-std::f32::consts::PI / 2.0)
+}
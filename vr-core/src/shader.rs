@@ -0,0 +1,72 @@
+use glow::HasContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderVersion {
+    /// OpenGL 4.1 core (native)
+    Gl410,
+    /// OpenGL ES 3.0 (WebGL2)
+    Es300,
+}
+impl ShaderVersion {
+    pub fn directive(&self) -> &'static str {
+        match self {
+            ShaderVersion::Gl410 => "#version 410",
+            ShaderVersion::Es300 => "#version 300 es",
+        }
+    }
+
+    pub fn precision(&self, shader_type: u32) -> &'static str {
+        match (self, shader_type) {
+            (ShaderVersion::Gl410, _) => "",
+            (ShaderVersion::Es300, glow::VERTEX_SHADER) => "precision highp float;",
+            (ShaderVersion::Es300, _) => "precision mediump float;",
+        }
+    }
+}
+
+/// Compiles and links a program from version-less shader sources.
+pub unsafe fn create_program<GL: HasContext>(
+    gl: &GL,
+    version: ShaderVersion,
+    shader_sources: &[(u32, &str)],
+) -> GL::Program {
+    let program = gl.create_program().expect("Cannot create program");
+
+    let mut shaders = Vec::with_capacity(shader_sources.len());
+
+    for (shader_type, shader_source) in shader_sources.iter() {
+        let shader = gl
+            .create_shader(*shader_type)
+            .expect("Cannot create shader");
+        // #extension directives have to precede the precision statements
+        let (extensions, body): (Vec<&str>, Vec<&str>) = shader_source
+            .lines()
+            .partition(|line| line.trim_start().starts_with("#extension"));
+        let source = format!(
+            "{}\n{}\n{}\n{}",
+            version.directive(),
+            extensions.join("\n"),
+            version.precision(*shader_type),
+            body.join("\n")
+        );
+        gl.shader_source(shader, &source);
+        gl.compile_shader(shader);
+        if !gl.get_shader_compile_status(shader) {
+            panic!("{}", gl.get_shader_info_log(shader));
+        }
+        gl.attach_shader(program, shader);
+        shaders.push(shader);
+    }
+
+    gl.link_program(program);
+    if !gl.get_program_link_status(program) {
+        panic!("{}", gl.get_program_info_log(program));
+    }
+
+    for shader in shaders {
+        gl.detach_shader(program, shader);
+        gl.delete_shader(shader);
+    }
+
+    program
+}
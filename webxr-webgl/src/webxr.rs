@@ -1,42 +1,81 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use futures_executor::LocalPool;
 use futures_util::task::LocalSpawnExt;
+use glam::f32::{vec2, Mat4, Vec2};
+use vr_core::{
+    level_pose, Button, ControllerState, FrameView, Hand, HandGestures, HandJoint, HandSkeleton,
+    HapticPulse, InputState, Joint, ReferenceSpace, SharedApp, View, Viewport, XrBackend,
+    HAND_JOINT_COUNT,
+};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::*;
 
+type ResetHandler = Closure<dyn FnMut(XrReferenceSpaceEvent)>;
+
 pub struct WebXR {
+    webgl2_context: WebGl2RenderingContext,
     pool: LocalPool,
-    running: bool,
+    /// Cleared by [`XrBackend::stop`] and when the session ends on its own.
+    running: Rc<Cell<bool>>,
+    /// Whether the XR layer framebuffer is multisampled.
+    pub antialias: bool,
+    /// Reference spaces to try in order, the first one granted is used.
+    pub reference_spaces: Vec<ReferenceSpace>,
     session: Rc<RefCell<Option<XrSession>>>,
     ref_space: Rc<RefCell<Option<XrReferenceSpace>>>,
+    /// Set by [`WebXR::recenter`], handled on the next frame.
+    recenter: Rc<Cell<bool>>,
+    /// The session's `end` handler, kept alive until the next session.
+    on_end: Rc<RefCell<Option<Closure<dyn FnMut(XrSessionEvent)>>>>,
+    /// The `reset` handler of `ref_space`.
+    on_reset: Rc<RefCell<Option<ResetHandler>>>,
 }
 impl WebXR {
-    pub fn new() -> WebXR {
+    pub fn new(webgl2_context: WebGl2RenderingContext) -> WebXR {
         WebXR {
+            webgl2_context,
             pool: LocalPool::new(),
-            running: false,
+            running: Rc::new(Cell::new(false)),
+            antialias: true,
+            reference_spaces: ReferenceSpace::ROOM_SCALE.to_vec(),
             session: Rc::new(RefCell::new(None)),
             ref_space: Rc::new(RefCell::new(None)),
+            recenter: Rc::new(Cell::new(false)),
+            on_end: Rc::new(RefCell::new(None)),
+            on_reset: Rc::new(RefCell::new(None)),
         }
     }
 
-    pub fn start(
-        &mut self,
-        webgl2_context: WebGl2RenderingContext,
-        mut frame_fn: impl FnMut(XrSession, Vec<XrView>, XrWebGlLayer, XrFrame, &XrReferenceSpace)
-            + 'static,
-    ) {
-        if self.running {
+    /// Replaces the tracking space with a local space whose origin is at
+    /// the head, facing where the head faces.
+    pub fn recenter(&mut self) {
+        self.recenter.set(true);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.get()
+    }
+}
+impl XrBackend<glow::Context> for WebXR {
+    fn start(&mut self, gl: Rc<glow::Context>, app: SharedApp<glow::Context>) {
+        if self.running.get() {
             return;
         }
-        self.running = true;
+        self.running.set(true);
 
-        let session = self.session.clone();
+        let webgl2_context = self.webgl2_context.clone();
+        let antialias = self.antialias;
+        let reference_spaces = self.reference_spaces.clone();
+        let running = self.running.clone();
+        let session_slot = self.session.clone();
         let ref_space = self.ref_space.clone();
+        let recenter = self.recenter.clone();
+        let on_end = self.on_end.clone();
+        let on_reset = self.on_reset.clone();
 
         self.pool
             .spawner()
@@ -53,49 +92,133 @@ impl WebXR {
                 }
 
                 let mut session_init = XrSessionInit::new();
-                session_init.optional_features(&JsValue::from_serde(&["bounded-floor"]).unwrap());
-                session.borrow_mut().replace(
+                session_init.optional_features(
+                    &JsValue::from_serde(&["bounded-floor", "local-floor", "hand-tracking"])
+                        .unwrap(),
+                );
+                let session: XrSession =
                     JsFuture::from(xr.request_session_with_options(session_mode, &session_init))
                         .await
                         .unwrap()
-                        .into(),
-                );
+                        .into();
+                session_slot.borrow_mut().replace(session.clone());
 
-                let borrowed_session = session.borrow();
-                let session = borrowed_session.as_ref().unwrap();
+                // the user or the browser may end the session, not only stop()
+                let ended = Closure::wrap(Box::new(move |_: XrSessionEvent| {
+                    running.set(false);
+                    session_slot.borrow_mut().take();
+                }) as Box<dyn FnMut(XrSessionEvent)>);
+                session.set_onend(Some(ended.as_ref().unchecked_ref()));
+                on_end.borrow_mut().replace(ended);
+                let session = &session;
 
-                let gl_layer =
-                    XrWebGlLayer::new_with_web_gl2_rendering_context(session, &webgl2_context)
-                        .unwrap();
+                let mut layer_init = XrWebGlLayerInit::new();
+                layer_init.antialias(antialias);
+                let gl_layer = XrWebGlLayer::new_with_web_gl2_rendering_context_and_layer_init(
+                    session,
+                    &webgl2_context,
+                    &layer_init,
+                )
+                .unwrap();
                 let mut render_state_init = XrRenderStateInit::new();
                 render_state_init.base_layer(Some(&gl_layer));
                 session.update_render_state_with_state(&render_state_init);
 
-                let space_type = XrReferenceSpaceType::BoundedFloor;
-                ref_space.borrow_mut().replace(
-                    JsFuture::from(session.request_reference_space(space_type))
+                let mut granted = None;
+                for &reference_space in &reference_spaces {
+                    let space_type = match reference_space {
+                        ReferenceSpace::BoundedFloor => XrReferenceSpaceType::BoundedFloor,
+                        ReferenceSpace::LocalFloor => XrReferenceSpaceType::LocalFloor,
+                        ReferenceSpace::Local => XrReferenceSpaceType::Local,
+                        ReferenceSpace::Viewer => XrReferenceSpaceType::Viewer,
+                    };
+                    if let Ok(space) =
+                        JsFuture::from(session.request_reference_space(space_type)).await
+                    {
+                        granted = Some((reference_space, space));
+                        break;
+                    }
+                }
+                let (reference_space, space) = match granted {
+                    Some(granted) => granted,
+                    None => {
+                        // ending the session hands the window back to the
+                        // desktop camera
+                        console::error_1(&"no preferred reference space is supported".into());
+                        let _p = session.end();
+                        return;
+                    }
+                };
+                console::log_1(&format!("reference space: {:?}", reference_space).into());
+                use_space(space.into(), &ref_space, &on_reset, &app);
+                let local_space: Option<XrReferenceSpace> =
+                    JsFuture::from(session.request_reference_space(XrReferenceSpaceType::Local))
                         .await
-                        .unwrap()
-                        .into(),
-                );
+                        .ok()
+                        .map(Into::into);
 
                 let f: Rc<RefCell<Option<Closure<dyn FnMut(f64, XrFrame)>>>> =
                     Rc::new(RefCell::new(None));
                 let g = f.clone();
-                let callback = Closure::wrap(Box::new(move |_time: f64, frame: XrFrame| {
+                let mut previous_input = InputState::default();
+                let mut gestures = HandGestures::default();
+                let mut last_time = None;
+                let callback = Closure::wrap(Box::new(move |time: f64, frame: XrFrame| {
                     let session = frame.session();
-                    let gl = webgl2_context.clone();
-
                     let gl_layer = session.render_state().base_layer().unwrap();
                     let swapchain_framebuffer = &gl_layer.framebuffer();
-                    gl.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
+                    webgl2_context.bind_framebuffer(glow::FRAMEBUFFER, Some(swapchain_framebuffer));
+
+                    if recenter.replace(false) {
+                        let level = local_space.as_ref().and_then(|local| {
+                            let head = frame.get_viewer_pose(local)?;
+                            let head = Mat4::from_cols_slice(&head.transform().matrix());
+                            Some((local, level_pose(head)))
+                        });
+                        if let Some((local, level)) = level {
+                            let space = local.get_offset_reference_space(&rigid_transform(level));
+                            use_space(space, &ref_space, &on_reset, &app);
+                        }
+                    }
 
                     let ref_space = ref_space.borrow();
                     let ref_space = ref_space.as_ref().unwrap();
                     let pose = frame.get_viewer_pose(ref_space).unwrap();
-                    let views = pose.views().iter().map(|v| v.into()).collect();
 
-                    frame_fn(session.clone(), views, gl_layer, frame, ref_space);
+                    let mut input = input_state(&session, &frame, ref_space, &previous_input);
+                    input.head = Some(Mat4::from_cols_slice(&pose.transform().matrix()));
+                    // animation frame times are in milliseconds
+                    input.dt = last_time.map_or(0.0, |last| ((time - last) / 1000.0) as f32);
+                    last_time = Some(time);
+                    gestures.apply(&mut input);
+                    let mut app = app.borrow_mut();
+                    input.transform(app.world_from_stage());
+                    app.update(&input);
+                    apply_haptics(&session, &app.take_haptics());
+                    previous_input = input;
+                    let world_from_stage = app.world_from_stage();
+
+                    for view in pose.views().iter() {
+                        let view: XrView = view.into();
+                        let viewport = gl_layer.get_viewport(&view).unwrap();
+                        let view_pose =
+                            world_from_stage * Mat4::from_cols_slice(&view.transform().matrix());
+                        let frame_view = FrameView {
+                            pose: view_pose,
+                            view: View {
+                                viewport: Viewport {
+                                    x: viewport.x(),
+                                    y: viewport.y(),
+                                    width: viewport.width(),
+                                    height: viewport.height(),
+                                },
+                                v_mat: view_pose.inverse(),
+                                p_mat: Mat4::from_cols_slice(&view.projection_matrix()),
+                            },
+                            framebuffer: None,
+                        };
+                        unsafe { app.draw_view(&gl, &frame_view) };
+                    }
 
                     session.request_animation_frame(
                         f.borrow().as_ref().unwrap().as_ref().unchecked_ref(),
@@ -109,17 +232,215 @@ impl WebXR {
             .expect("Failed to start application");
     }
 
-    pub fn stop(&mut self) {
-        if !self.running {
+    fn stop(&mut self) {
+        if !self.running.get() {
             return;
         }
         if let Some(session) = self.session.borrow().as_ref() {
             let _p = session.end();
-            self.running = false;
+            self.running.set(false);
         }
     }
 
-    pub fn process_events(&mut self, _control_flow: &mut winit::event_loop::ControlFlow) {
+    fn poll(&mut self) -> bool {
         self.pool.try_run_one();
+        true
+    }
+}
+
+/// Makes `space` the tracking space and tells the app about its play area
+/// and its `reset` events, which fire when the user recenters or the play
+/// area is redrawn.
+fn use_space(
+    space: XrReferenceSpace,
+    ref_space: &RefCell<Option<XrReferenceSpace>>,
+    on_reset: &RefCell<Option<ResetHandler>>,
+    app: &SharedApp<glow::Context>,
+) {
+    // the replaced space would keep reporting resets to a dropped handler
+    if let Some(previous) = ref_space.borrow_mut().take() {
+        previous.set_onreset(None);
+    }
+    let handler = reset_handler(app);
+    space.set_onreset(Some(handler.as_ref().unchecked_ref()));
+    app.borrow_mut().play_area_changed(&play_area(&space));
+    ref_space.borrow_mut().replace(space);
+    on_reset.borrow_mut().replace(handler);
+}
+
+fn reset_handler(app: &SharedApp<glow::Context>) -> ResetHandler {
+    let app = app.clone();
+    Closure::wrap(Box::new(move |event: XrReferenceSpaceEvent| {
+        let previous_from_new = event
+            .transform()
+            .map(|transform| Mat4::from_cols_slice(&transform.matrix()));
+        let mut app = app.borrow_mut();
+        app.reference_space_changed(previous_from_new);
+        app.play_area_changed(&play_area(&event.reference_space()));
+    }) as Box<dyn FnMut(XrReferenceSpaceEvent)>)
+}
+
+/// Floor polygon of a bounded-floor space as (x, z), empty for any other.
+fn play_area(space: &XrReferenceSpace) -> Vec<Vec2> {
+    match space.dyn_ref::<XrBoundedReferenceSpace>() {
+        Some(bounded) => bounded
+            .bounds_geometry()
+            .iter()
+            .map(|point| {
+                let point: DomPointReadOnly = point.unchecked_into();
+                vec2(point.x() as f32, point.z() as f32)
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn rigid_transform(pose: Mat4) -> XrRigidTransform {
+    let (_, rotation, translation) = pose.to_scale_rotation_translation();
+    let mut position = DomPointInit::new();
+    position
+        .x(translation.x as f64)
+        .y(translation.y as f64)
+        .z(translation.z as f64);
+    let mut orientation = DomPointInit::new();
+    orientation
+        .x(rotation.x as f64)
+        .y(rotation.y as f64)
+        .z(rotation.z as f64)
+        .w(rotation.w as f64);
+    XrRigidTransform::new_with_position_and_orientation(&position, &orientation).unwrap()
+}
+
+/// Reads controllers through their `xr-standard` gamepad mapping. That
+/// mapping reserves the menu button for the system, so `menu` never fires.
+pub fn input_state(
+    session: &XrSession,
+    frame: &XrFrame,
+    ref_space: &XrReferenceSpace,
+    previous: &InputState,
+) -> InputState {
+    let mut input = InputState::default();
+    let sources = session.input_sources();
+    for i in 0..sources.length() {
+        let source = sources.get(i).unwrap();
+        let hand = match source.handedness() {
+            XrHandedness::Left => Hand::Left,
+            XrHandedness::Right => Hand::Right,
+            _ => continue,
+        };
+        let grip = source
+            .grip_space()
+            .and_then(|space| frame.get_pose(&space, ref_space))
+            .map(|pose| Mat4::from_cols_slice(&pose.transform().matrix()));
+        let aim = frame
+            .get_pose(&source.target_ray_space(), ref_space)
+            .map(|pose| Mat4::from_cols_slice(&pose.transform().matrix()));
+        let mut state = ControllerState {
+            grip,
+            aim,
+            skeleton: hand_skeleton(&source, frame, ref_space),
+            ..Default::default()
+        };
+        if let Some(gamepad) = source.gamepad() {
+            let previous = previous.hand(hand);
+            let buttons = gamepad.buttons();
+            let button = |index: u32| buttons.get(index).dyn_into::<GamepadButton>().ok();
+            let pressed = |index: u32| button(index).is_some_and(|button| button.pressed());
+            let value = |index: u32| button(index).map_or(0.0, |button| button.value() as f32);
+            let axes = gamepad.axes();
+            let axis = |index: u32| axes.get(index).as_f64().unwrap_or(0.0) as f32;
+
+            state.select = Button::new(pressed(0), previous.select);
+            state.primary = Button::new(pressed(4), previous.primary);
+            state.secondary = Button::new(pressed(5), previous.secondary);
+            state.trigger = value(0);
+            state.squeeze = value(1);
+            // gamepad Y points down
+            state.thumbstick = vec2(axis(2), -axis(3));
+        }
+        match hand {
+            Hand::Left => input.left = state,
+            Hand::Right => input.right = state,
+        }
+    }
+    input
+}
+
+/// Locates every joint of the source's `XRHand`, `None` for controllers or
+/// while any joint is lost. web-sys has no bindings for `XRInputSource.hand`
+/// and `XRFrame.getJointPose` yet, so they are called by name.
+fn hand_skeleton(
+    source: &XrInputSource,
+    frame: &XrFrame,
+    ref_space: &XrReferenceSpace,
+) -> Option<HandSkeleton> {
+    let hand = js_sys::Reflect::get(source, &"hand".into()).ok()?;
+    if hand.is_undefined() || hand.is_null() {
+        return None;
+    }
+    let get = js_sys::Reflect::get(&hand, &"get".into()).ok()?;
+    let get: &js_sys::Function = get.dyn_ref()?;
+    let get_joint_pose = js_sys::Reflect::get(frame, &"getJointPose".into()).ok()?;
+    let get_joint_pose: &js_sys::Function = get_joint_pose.dyn_ref()?;
+
+    let mut joints = [Joint {
+        pose: Mat4::IDENTITY,
+        radius: 0.0,
+    }; HAND_JOINT_COUNT];
+    for joint in HandJoint::ALL {
+        let name = match joint.webxr_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let space = get.call1(&hand, &name.into()).ok()?;
+        let pose = get_joint_pose.call2(frame, &space, ref_space).ok()?;
+        if pose.is_undefined() || pose.is_null() {
+            return None;
+        }
+        let radius = js_sys::Reflect::get(&pose, &"radius".into())
+            .ok()?
+            .as_f64()?;
+        let pose: XrPose = pose.unchecked_into();
+        joints[joint as usize] = Joint {
+            pose: Mat4::from_cols_slice(&pose.transform().matrix()),
+            radius: radius as f32,
+        };
+    }
+
+    // WebXR has no palm, place it the way OpenXR does
+    let metacarpal = joints[HandJoint::MiddleMetacarpal as usize];
+    let proximal = joints[HandJoint::MiddleProximal as usize];
+    let mut palm = metacarpal;
+    palm.pose.w_axis = (metacarpal.pose.w_axis + proximal.pose.w_axis) / 2.0;
+    joints[HandJoint::Palm as usize] = palm;
+
+    Some(HandSkeleton { joints })
+}
+
+pub fn apply_haptics(session: &XrSession, pulses: &[HapticPulse]) {
+    if pulses.is_empty() {
+        return;
+    }
+    let sources = session.input_sources();
+    for pulse in pulses {
+        let handedness = match pulse.hand {
+            Hand::Left => XrHandedness::Left,
+            Hand::Right => XrHandedness::Right,
+        };
+        let actuator = (0..sources.length())
+            .filter_map(|i| sources.get(i))
+            .filter(|source| source.handedness() == handedness)
+            .filter_map(|source| source.gamepad())
+            .find_map(|gamepad| {
+                gamepad
+                    .haptic_actuators()
+                    .get(0)
+                    .dyn_into::<GamepadHapticActuator>()
+                    .ok()
+            });
+        if let Some(actuator) = actuator {
+            // web gamepads have no frequency control
+            let _p = actuator.pulse(pulse.amplitude as f64, pulse.duration as f64 * 1000.0);
+        }
     }
 }